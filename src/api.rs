@@ -0,0 +1,178 @@
+//! Requests to any API endpoint, for endpoints that don't have a command.
+
+use crate::http::{ApiClient, RawResponse};
+
+use anyhow::{Context, Error, Result};
+use reqwest::header::LINK;
+use reqwest::Method;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Send a request and return the response. Fields are sent as a JSON
+/// object, or in the query string for GET requests and when the body is
+/// read from `input`.
+///
+/// With `paginate`, pages linked with `rel="next"` are fetched too. Pages
+/// that are arrays are combined into one, otherwise each page is returned.
+/// Links back to a page that was already fetched are an error.
+pub fn call(
+    api: &ApiClient,
+    method: &str,
+    path: &str,
+    fields: &[String],
+    input: Option<&Path>,
+    paginate: bool,
+) -> Result<Vec<Value>> {
+    let method = Method::from_bytes(method.to_uppercase().as_bytes())
+        .map_err(|_| Error::msg(format!("Invalid HTTP method {method}")))?;
+    if paginate && method != Method::GET {
+        return Err(Error::msg("--paginate can only be used with GET requests"));
+    }
+
+    let mut fields = fields
+        .iter()
+        .map(|field| parse_field(field))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut url = api_url(api, path)?;
+    let body = match input {
+        Some(input) => Some(read_input(input)?),
+        None if method == Method::GET => None,
+        None if fields.is_empty() => None,
+        None => {
+            let object: Map<String, Value> = fields
+                .drain(..)
+                .map(|(key, value)| (key, Value::String(value)))
+                .collect();
+            Some(Value::Object(object))
+        }
+    };
+    if !fields.is_empty() {
+        url.query_pairs_mut().extend_pairs(&fields);
+    }
+
+    let mut pages = vec![];
+    let mut fetched = HashSet::new();
+    loop {
+        let resp = api.request(method.clone(), url.clone(), body.as_ref())?;
+        let next = match paginate {
+            true => next_page(api, &url, &resp)?,
+            false => None,
+        };
+        pages.extend(parse_body(resp));
+        fetched.insert(url);
+
+        match next {
+            Some(next) if fetched.contains(&next) => {
+                return Err(Error::msg(format!(
+                    "Pagination loop, the next page {next} was already fetched"
+                )))
+            }
+            Some(next) => url = next,
+            None => break,
+        }
+    }
+
+    if pages.len() > 1 && pages.iter().all(Value::is_array) {
+        let items = pages
+            .into_iter()
+            .flat_map(|page| match page {
+                Value::Array(items) => items,
+                _ => unreachable!("all pages are arrays"),
+            })
+            .collect();
+        return Ok(vec![Value::Array(items)]);
+    }
+    Ok(pages)
+}
+
+fn parse_field(field: &str) -> Result<(String, String)> {
+    match field.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(Error::msg(format!(
+            "Invalid field {field}, expected key=value"
+        ))),
+    }
+}
+
+/// Read a JSON body from a file, or from stdin when the path is `-`
+fn read_input(path: &Path) -> Result<Value> {
+    let mut input = String::new();
+    if path == Path::new("-") {
+        io::stdin().read_to_string(&mut input)?;
+    } else {
+        input = fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+    }
+    serde_json::from_str(&input).with_context(|| format!("{} is not valid JSON", path.display()))
+}
+
+/// The URL of a path given on the command line, such as
+/// `/api/v1/manifests?page=2`, which is used as it is written under the
+/// gateway's path prefix
+fn api_url(api: &ApiClient, path: &str) -> Result<url::Url> {
+    // The empty segment keeps the trailing slash of the prefix to resolve against
+    let base = api.endpoint(&[""]);
+    Ok(base.join(&format!("./{}", path.trim_start_matches('/')))?)
+}
+
+/// Parse the body of a response as JSON, or as text when it isn't JSON. An
+/// empty body has no value.
+fn parse_body(resp: RawResponse) -> Option<Value> {
+    if resp.body.is_empty() {
+        return None;
+    }
+    if resp.is_json() {
+        if let Ok(value) = serde_json::from_slice(&resp.body) {
+            return Some(value);
+        }
+    }
+    Some(Value::String(
+        String::from_utf8_lossy(&resp.body).to_string(),
+    ))
+}
+
+/// The URL of the next page from a `Link` header, eg
+/// `<https://gateway/api/v1/manifests?page=2>; rel="next"`. Relative links
+/// are resolved against the URL of the current page.
+fn next_page(api: &ApiClient, current: &url::Url, resp: &RawResponse) -> Result<Option<url::Url>> {
+    let links = resp
+        .headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+
+    for link in links {
+        let mut parts = link.split(';').map(str::trim);
+        let target = match parts.next() {
+            Some(target) if target.starts_with('<') && target.ends_with('>') => {
+                &target[1..target.len() - 1]
+            }
+            _ => continue,
+        };
+        let is_next = parts.any(|param| {
+            param
+                .strip_prefix("rel=")
+                .is_some_and(|rel| rel.trim_matches('"').split(' ').any(|rel| rel == "next"))
+        });
+        if !is_next {
+            continue;
+        }
+
+        let gateway = &api.config().url;
+        let url = current.join(target)?;
+        let base = gateway.path().trim_end_matches('/');
+        return match url.path().strip_prefix(base) {
+            Some(next) if url.origin() == gateway.origin() && next.starts_with('/') => {
+                Ok(Some(url))
+            }
+            _ => Err(Error::msg(format!("Next page {url} is not on the gateway"))),
+        };
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,181 @@
+//! Cassettes of recorded HTTP exchanges, used by `--record` and `--replay`
+//! to reproduce a session with the gateway without the network.
+
+use crate::http::RawResponse;
+use crate::redact;
+
+use anyhow::{Context, Error, Result};
+use reqwest::blocking::Request;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+/// A request and the response the gateway sent to it
+#[derive(Debug, Serialize, Deserialize)]
+struct Interaction {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    body: String,
+}
+
+/// Whether requests are recorded to a cassette or replayed from it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Record,
+    Replay,
+}
+
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    file: CassetteFile,
+    replayed: Vec<bool>,
+}
+
+impl Cassette {
+    /// Start recording to a new cassette, replacing the file if it exists
+    pub fn record(path: &Path) -> Result<Cassette> {
+        let cassette = Cassette {
+            path: path.to_path_buf(),
+            mode: Mode::Record,
+            file: CassetteFile::default(),
+            replayed: vec![],
+        };
+        cassette.save()?;
+        Ok(cassette)
+    }
+
+    /// Load a cassette to replay
+    pub fn replay(path: &Path) -> Result<Cassette> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Could not read cassette {}", path.display()))?;
+        let file: CassetteFile = serde_yaml::from_str(&data)
+            .with_context(|| format!("Invalid cassette {}", path.display()))?;
+        Ok(Cassette {
+            path: path.to_path_buf(),
+            mode: Mode::Replay,
+            replayed: vec![false; file.interactions.len()],
+            file,
+        })
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Add an exchange to the cassette and save it. Credentials in headers
+    /// and JSON bodies are redacted.
+    pub fn add(&mut self, request: &Request, response: &RawResponse) -> Result<()> {
+        let request = RecordedRequest {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            headers: headers(request.headers()),
+            body: request
+                .body()
+                .and_then(|body| body.as_bytes())
+                .map(redact::body),
+        };
+        let response = RecordedResponse {
+            status: response.status.as_u16(),
+            headers: headers(&response.headers),
+            body: redact::body(&response.body),
+        };
+
+        self.file
+            .interactions
+            .push(Interaction { request, response });
+        self.save()
+    }
+
+    /// Return the recorded response to a request. Requests are matched by
+    /// method, path and query, so a cassette can be replayed against any
+    /// gateway URL. Each recorded response is only replayed once.
+    pub fn find(&mut self, request: &Request) -> Result<RawResponse> {
+        let url = request.url();
+        let position = self
+            .file
+            .interactions
+            .iter()
+            .zip(&self.replayed)
+            .position(|(interaction, replayed)| {
+                !replayed
+                    && interaction.request.method == request.method().as_str()
+                    && url::Url::parse(&interaction.request.url).is_ok_and(|recorded| {
+                        recorded.path() == url.path() && recorded.query() == url.query()
+                    })
+            })
+            .ok_or_else(|| {
+                Error::msg(format!(
+                    "No recorded response to {} {} in {}",
+                    request.method(),
+                    url,
+                    self.path.display()
+                ))
+            })?;
+        self.replayed[position] = true;
+
+        let response = &self.file.interactions[position].response;
+        let mut headers = HeaderMap::new();
+        for (name, value) in &response.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+        Ok(RawResponse {
+            status: StatusCode::from_u16(response.status)?,
+            headers,
+            body: response.body.clone().into_bytes(),
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        let data = serde_yaml::to_string(&self.file)?;
+        fs::write(&self.path, data)
+            .with_context(|| format!("Could not write cassette {}", self.path.display()))
+    }
+}
+
+/// Redacted headers, with repeated headers joined by commas
+fn headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    let mut recorded: BTreeMap<String, String> = BTreeMap::new();
+    for (name, value) in headers {
+        let value = redact::header(name, value);
+        recorded
+            .entry(name.to_string())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(&value);
+            })
+            .or_insert(value);
+    }
+    recorded
+}
@@ -0,0 +1,92 @@
+//! Versions of the JumpWire gateway.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::command;
+use crate::http::ApiClient;
+
+use anyhow::{Error, Result};
+
+/// A gateway version such as `4.1.0`. Pre-release and build suffixes are
+/// ignored when comparing versions.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Version {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl FromStr for Version {
+    type Err = Error;
+
+    /// Parse a version such as `4.1.0`, `v4.1` or `4.1.0-rc.1`
+    fn from_str(s: &str) -> Result<Version> {
+        let release = s
+            .trim()
+            .trim_start_matches('v')
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default();
+
+        let numbers: Vec<u64> = release
+            .split('.')
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| Error::msg(format!("Invalid version {s:?}")))?;
+        match numbers[..] {
+            [major] => Ok(Version::new(major, 0, 0)),
+            [major, minor] => Ok(Version::new(major, minor, 0)),
+            [major, minor, patch] => Ok(Version::new(major, minor, patch)),
+            _ => Err(Error::msg(format!("Invalid version {s:?}"))),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The version of the gateway, from its status. Returns `None` when the
+/// status doesn't include a version.
+pub fn server_version(api: &ApiClient) -> Result<Option<Version>> {
+    let status = command::status(api)?;
+    status.version.as_deref().map(str::parse).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_versions() {
+        assert_eq!("4.1.0".parse::<Version>().unwrap(), Version::new(4, 1, 0));
+        assert_eq!("v4.1".parse::<Version>().unwrap(), Version::new(4, 1, 0));
+        assert_eq!("4".parse::<Version>().unwrap(), Version::new(4, 0, 0));
+        assert_eq!(
+            "4.1.0-rc.1+abc".parse::<Version>().unwrap(),
+            Version::new(4, 1, 0)
+        );
+        assert!("".parse::<Version>().is_err());
+        assert!("4.x".parse::<Version>().is_err());
+        assert!("1.2.3.4".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn versions_are_compared_numerically() {
+        assert!(Version::new(4, 10, 0) > Version::new(4, 9, 9));
+        assert!(Version::new(10, 0, 0) > Version::new(9, 99, 99));
+    }
+}
@@ -1,83 +1,998 @@
+use std::collections::BTreeMap;
+use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use crate::Args;
+use crate::credentials::{self, CredentialStore, StoreKind};
+use crate::permissions;
+use crate::redact;
+use crate::tls;
+use crate::yaml_edit;
 use anyhow::{Error, Result};
 // use re-exported version of `CookieStore` for crate compatibility
+use config::Source;
 use reqwest_cookie_store::{CookieStore, CookieStoreMutex};
-use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-#[derive(Deserialize, Debug)]
+#[derive(Clone, Deserialize, Serialize, Debug)]
+#[non_exhaustive]
 pub struct Config {
     pub url: url::Url,
     pub token: Option<String>,
+
+    /// Name of the context selected from the configuration file, if any
+    pub context: Option<String>,
+
+    /// Where the authentication token is stored between invocations
+    #[serde(default)]
+    pub credential_store: StoreKind,
+
+    /// Program used to store tokens with the `helper` credential store
+    pub credential_helper: Option<String>,
+
+    /// PEM file with additional CA certificates to trust
+    pub ca_cert: Option<PathBuf>,
+
+    /// PEM file with a client certificate for mutual TLS
+    pub client_cert: Option<PathBuf>,
+
+    /// PEM file with the private key for `client_cert`
+    pub client_key: Option<PathBuf>,
+
+    /// Disable verification of the gateway's TLS certificate
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+
+    /// Base64 encoded SHA-256 hashes of public keys, one of which must be
+    /// present in the gateway's certificate chain
+    #[serde(default, deserialize_with = "string_list")]
+    pub spki_pins: Vec<String>,
+
+    /// Seconds to wait for a connection to the gateway, 0 to wait forever
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+
+    /// Seconds to wait for a request to complete, 0 to wait forever
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+
+    /// How many times idempotent requests are retried when the gateway is
+    /// unreachable or temporarily unavailable
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+
+    /// HTTP proxy used for all requests to the gateway
+    pub proxy: Option<url::Url>,
+
+    /// Comma separated hosts that are reached without going through the proxy
+    pub no_proxy: Option<String>,
+
+    /// Extra headers sent with every request
+    #[serde(default, deserialize_with = "header_map")]
+    pub headers: BTreeMap<String, String>,
+
+    /// Directories whose `.jwctl.yaml` files can set any configuration key
+    #[serde(default, deserialize_with = "string_list")]
+    pub trusted_projects: Vec<String>,
+
+    /// Send reads, but print the first request that could change anything
+    /// instead of sending it and stop
+    #[serde(skip)]
+    pub dry_run: bool,
+
+    /// Show request bodies in previews as is, without redacting credentials
+    #[serde(skip)]
+    pub raw_body: bool,
+
+    /// Print each request as an equivalent curl command
+    #[serde(skip)]
+    pub print_curl: bool,
+
+    /// How many times `--verbose` was given
+    #[serde(skip)]
+    pub verbosity: u8,
+
+    /// Cassette file to record requests and responses to
+    #[serde(skip)]
+    pub record: Option<PathBuf>,
+
+    /// Cassette file to replay responses from instead of sending requests
+    #[serde(skip)]
+    pub replay: Option<PathBuf>,
+
+    /// Where each configuration value was loaded from
+    #[serde(skip)]
+    pub origins: BTreeMap<String, Origin>,
+}
+
+/// Deserialize a list from either a sequence or a comma separated string,
+/// as lists can't be expressed directly in environment variables.
+fn string_list<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum List {
+        Items(Vec<String>),
+        String(String),
+    }
+
+    let items = match List::deserialize(deserializer)? {
+        List::Items(items) => items,
+        List::String(s) => s.split(',').map(String::from).collect(),
+    };
+    Ok(items
+        .into_iter()
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect())
+}
+
+/// Deserialize headers from either a map or a comma separated string of
+/// `Name: value` pairs, as maps can't be expressed in environment variables.
+fn header_map<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Headers {
+        Map(BTreeMap<String, String>),
+        String(String),
+    }
+
+    match Headers::deserialize(deserializer)? {
+        Headers::Map(headers) => Ok(headers),
+        Headers::String(s) => parse_headers(&s).map_err(serde::de::Error::custom),
+    }
+}
+
+/// Parse a comma separated string of `Name: value` headers
+fn parse_headers(s: &str) -> Result<BTreeMap<String, String>> {
+    s.split(',')
+        .filter(|header| !header.trim().is_empty())
+        .map(|header| match header.split_once(':') {
+            Some((name, value)) => Ok((name.trim().to_string(), value.trim().to_string())),
+            None => Err(Error::msg(format!(
+                "Invalid header {header}, expected `Name: value`"
+            ))),
+        })
+        .collect()
+}
+
+fn default_connect_timeout() -> u64 {
+    10
+}
+
+fn default_timeout() -> u64 {
+    30
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+/// A configuration layer that values can be loaded from
+#[derive(Clone, Debug)]
+pub enum Origin {
+    File(PathBuf),
+    CredentialStore(String),
+    Context(String),
+    Env,
+    Flag,
+}
+
+impl Origin {
+    /// Describe where the value for `key` was set
+    pub fn describe(&self, key: &str) -> String {
+        match self {
+            Origin::File(path) => format!("file {}", path.display()),
+            Origin::CredentialStore(store) => format!("credential store {store}"),
+            Origin::Context(name) => format!("context {name}"),
+            Origin::Env => format!("env JW_{}", key.to_uppercase()),
+            Origin::Flag => format!("flag --{}", key.replace('_', "-")),
+        }
+    }
+}
+
+/// Configuration keys holding values that should not be displayed by default
+const SECRET_KEYS: &[&str] = &["token", "headers"];
+
+/// Configuration keys holding URLs, whose user and password are not
+/// displayed by default
+const URL_KEYS: &[&str] = &["url", "proxy"];
+
+impl Config {
+    /// Create a configuration for a gateway with no other settings
+    pub fn new(url: url::Url) -> Config {
+        Config {
+            url,
+            token: None,
+            context: None,
+            credential_store: StoreKind::default(),
+            credential_helper: None,
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            insecure_skip_verify: false,
+            spki_pins: vec![],
+            connect_timeout: default_connect_timeout(),
+            timeout: default_timeout(),
+            retries: default_retries(),
+            proxy: None,
+            no_proxy: None,
+            headers: BTreeMap::new(),
+            trusted_projects: vec![],
+            dry_run: false,
+            raw_body: false,
+            print_curl: false,
+            verbosity: 0,
+            record: None,
+            replay: None,
+            origins: BTreeMap::new(),
+        }
+    }
+
+    /// Open the credential store used for this configuration
+    pub fn credential_store(&self) -> Result<Box<dyn CredentialStore>> {
+        credentials::open(
+            &self.credential_store,
+            self.credential_helper.as_deref(),
+            Some(&self.url),
+            &self.gateway_dir()?,
+        )
+    }
+
+    /// Return the directory holding the cookies and token for this gateway
+    pub fn gateway_dir(&self) -> Result<PathBuf> {
+        gateway_dir(self.context.as_deref(), Some(&self.url))
+    }
+
+    /// Return where the value of a key was loaded from. For maps, this is
+    /// the origin of one of its entries.
+    pub fn origin(&self, key: &str) -> Option<&Origin> {
+        let prefix = format!("{key}.");
+        self.origins.get(key).or_else(|| {
+            self.origins
+                .iter()
+                .find(|(k, _)| k.starts_with(&prefix))
+                .map(|(_, origin)| origin)
+        })
+    }
+
+    /// Return each effective configuration value as a key and its display
+    /// value, with secrets redacted unless `show_secrets` is set.
+    pub fn values(&self, show_secrets: bool) -> Result<Vec<(String, String)>> {
+        let values = match serde_json::to_value(self)? {
+            serde_json::Value::Object(values) => values,
+            _ => return Err(Error::msg("Configuration is not a map")),
+        };
+
+        let values = values
+            .into_iter()
+            .map(|(key, value)| {
+                let display = match value {
+                    serde_json::Value::Null => "~".to_string(),
+                    _ if SECRET_KEYS.contains(&key.as_str()) && !show_secrets => {
+                        "<redacted>".to_string()
+                    }
+                    serde_json::Value::String(s) if URL_KEYS.contains(&key.as_str()) => {
+                        match show_secrets {
+                            true => s,
+                            false => redact::userinfo(&s),
+                        }
+                    }
+                    serde_json::Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                (key, display)
+            })
+            .collect();
+
+        Ok(values)
+    }
+}
+
+/// Values that take precedence over every other source of configuration,
+/// such as the CLI's flags
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Overrides {
+    pub url: Option<url::Url>,
+    pub token: Option<String>,
+    pub context: Option<String>,
+    /// Configuration file to use instead of the default one
+    pub config: Option<PathBuf>,
+    pub retries: Option<u32>,
+    pub ca_cert: Option<PathBuf>,
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+    pub insecure_skip_verify: bool,
+    pub spki_pins: Vec<String>,
+    pub dry_run: bool,
+    pub raw_body: bool,
+    pub print_curl: bool,
+    pub verbosity: u8,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+}
+
+impl config::Source for Overrides {
+    fn clone_into_box(&self) -> Box<dyn config::Source + Send + Sync> {
+        Box::new((*self).clone())
+    }
+
+    fn collect(&self) -> Result<config::Map<String, config::Value>, config::ConfigError> {
+        let mut m = config::Map::new();
+
+        if let Some(url) = &self.url {
+            let value = config::ValueKind::String(url.to_string());
+            m.insert("url".to_string(), value.into());
+        }
+
+        if let Some(token) = &self.token {
+            let value = config::ValueKind::String(token.to_string());
+            m.insert("token".to_string(), value.into());
+        }
+
+        if let Some(context) = &self.context {
+            let value = config::ValueKind::String(context.to_string());
+            m.insert("context".to_string(), value.into());
+        }
+
+        if let Some(retries) = self.retries {
+            let value = config::ValueKind::U64(retries.into());
+            m.insert("retries".to_string(), value.into());
+        }
+
+        let paths = [
+            ("ca_cert", &self.ca_cert),
+            ("client_cert", &self.client_cert),
+            ("client_key", &self.client_key),
+        ];
+        for (key, path) in paths {
+            if let Some(path) = path {
+                let value = config::ValueKind::String(path.display().to_string());
+                m.insert(key.to_string(), value.into());
+            }
+        }
+
+        if self.insecure_skip_verify {
+            let value = config::ValueKind::Boolean(true);
+            m.insert("insecure_skip_verify".to_string(), value.into());
+        }
+
+        if !self.spki_pins.is_empty() {
+            let pins = self
+                .spki_pins
+                .iter()
+                .map(|pin| pin.as_str().into())
+                .collect();
+            let value = config::ValueKind::Array(pins);
+            m.insert("spki_pins".to_string(), value.into());
+        }
+
+        Ok(m)
+    }
+}
+
+/// A named context defined in the configuration file
+#[derive(Debug, Serialize)]
+pub struct ContextInfo {
+    pub name: String,
+    pub url: Option<String>,
+    pub current: bool,
 }
 
-const TOKEN_FILE: &str = ".token";
 const CONFIG_FILE: &str = "config.yaml";
+const PROJECT_CONFIG_FILE: &str = ".jwctl.yaml";
+const COOKIE_FILE: &str = "cookies.json";
+const CONTEXTS_DIR: &str = "contexts";
+const GATEWAYS_DIR: &str = "gateways";
+
+/// Files shared by all gateways in earlier versions
+const LEGACY_FILES: &[&str] = &[
+    credentials::TOKEN_FILE,
+    credentials::ENCRYPTED_TOKEN_FILE,
+    COOKIE_FILE,
+];
+
+/// Keys a project configuration file can set without its directory being
+/// listed in `trusted_projects`. None of them can run programs, weaken TLS
+/// or send the stored token anywhere but the gateway it was stored for.
+const PROJECT_KEYS: &[&str] = &[
+    "url",
+    "context",
+    "current_context",
+    "connect_timeout",
+    "timeout",
+    "retries",
+];
 
 /// Load and merge configuration from multiple sources. In decreasing
 /// preference order, configuration values are loaded from:
 /// - command line options
 /// - env vars prefixed with `JW_`
-/// - ~/.config/jwctl/config.yaml
-pub fn load_config(args: Args) -> Result<Config> {
+/// - a `.jwctl.yaml` file in the current directory or one of its parents,
+///   limited to `PROJECT_KEYS` unless its directory is trusted
+/// - the selected context in ~/.config/jwctl/config.yaml
+/// - the credential store, eg ~/.config/jwctl/.token
+/// - ~/.config/jwctl/config.yaml, or the file passed with `--config`
+///
+/// The context is selected with `--context`, `JW_CONTEXT` or the
+/// `current_context` key of the configuration files.
+pub fn load_config(args: Overrides) -> Result<Config> {
     let dir_path = config_dir()?;
     debug!("Loading configuration from {:?}", dir_path);
+    permissions::warn_if_loose(&dir_path);
 
-    let config_path = config_file()?;
+    let config_path = config_file(args.config.as_deref())?;
+    let project_path = find_project_file();
+    let (dry_run, raw_body) = (args.dry_run, args.raw_body);
+    let (print_curl, verbosity) = (args.print_curl, args.verbosity);
+    let (record, replay) = (args.record.clone(), args.replay.clone());
 
-    // Create a config from the token file, if it exists, and
-    // merge it into the main config.
-    let mut token_path = dir_path.clone();
-    token_path.push(TOKEN_FILE);
-    let token = fs::read_to_string(token_path).ok();
-    let token_config = config::Config::builder()
-        .set_override_option("token", token)?
+    let file_config = file_layer(&config_path)?;
+    migrate_legacy_files(get_optional::<url::Url>(&file_config, "url")?.as_ref())?;
+    let env_config = config::Config::builder()
+        .add_source(config::Environment::with_prefix("JW"))
         .build()?;
+    let args_config = config::Config::builder().add_source(args).build()?;
 
-    let config = config::Config::builder()
-        .add_source(config::File::from(config_path).required(false))
-        .add_source(token_config)
-        .add_source(config::Environment::with_prefix("JW"))
-        .add_source(args)
+    let user_config = config::Config::builder()
+        .add_source(file_config.clone())
+        .add_source(env_config.clone())
+        .add_source(args_config.clone())
+        .build()?;
+    let trusted = match &project_path {
+        Some(path) => is_trusted(&user_config, path)?,
+        None => false,
+    };
+    let mut project_config = match &project_path {
+        Some(path) => project_layer(path, trusted)?,
+        None => config::Config::default(),
+    };
+
+    let base = config::Config::builder()
+        .add_source(file_config.clone())
+        .add_source(project_config.clone())
+        .add_source(env_config.clone())
+        .add_source(args_config.clone())
+        .build()?;
+    let context = selected_context(&base)?;
+    let context_config = context_config(&base, context.as_deref())?;
+
+    // The token stored for a context must only be sent to that context's URL
+    if let (Some(path), Some(name), false) = (&project_path, &context, trusted) {
+        let mut values = project_config.collect()?;
+        if values.remove("url").is_some() {
+            warn!("Ignoring url from {path:?} because context {name} is selected");
+            project_config = layer_from(values)?;
+        }
+    }
+
+    // Load the token from the configured credential store, which may
+    // itself be configured by the selected context. A token from a layer
+    // above the store, eg `--token`, wins anyway, so the store isn't read
+    // at all rather than asking for a passphrase or running a helper.
+    let selected = config::Config::builder()
+        .add_source(base)
+        .add_source(context_config.clone())
+        .add_source(project_config.clone())
+        .add_source(env_config.clone())
+        .add_source(args_config.clone())
+        .build()?;
+    let overriding = config::Config::builder()
+        .add_source(context_config.clone())
+        .add_source(project_config.clone())
+        .add_source(env_config.clone())
+        .add_source(args_config.clone())
+        .build()?;
+
+    // Layers in increasing order of precedence
+    let mut layers = vec![(Origin::File(config_path.clone()), file_config)];
+    if get_optional::<String>(&overriding, "token")?.is_none() {
+        let url: Option<url::Url> = get_optional(&selected, "url")?;
+        let store = credentials::open(
+            &get_optional(&selected, "credential_store")?.unwrap_or_default(),
+            get_optional::<String>(&selected, "credential_helper")?.as_deref(),
+            url.as_ref(),
+            &gateway_dir(context.as_deref(), url.as_ref())?,
+        )?;
+        let token_config = config::Config::builder()
+            .set_override_option("token", store.get()?)?
+            .build()?;
+        layers.push((Origin::CredentialStore(store.describe()), token_config));
+    }
+    layers.push((
+        Origin::Context(context.clone().unwrap_or_default()),
+        context_config,
+    ));
+    if let Some(path) = project_path {
+        layers.push((Origin::File(path), project_config));
+    }
+    layers.push((Origin::Env, env_config));
+    layers.push((Origin::Flag, args_config));
+
+    // Track which layer each effective value comes from
+    let mut origins = BTreeMap::new();
+    let mut builder = config::Config::builder();
+    for (origin, layer) in layers {
+        for key in flatten_keys(layer.collect()?, "") {
+            origins.insert(key, origin.clone());
+        }
+        builder = builder.add_source(layer);
+    }
+
+    // A context selected through `current_context` comes from the file
+    if context.is_some() {
+        origins
+            .entry("context".to_string())
+            .or_insert(Origin::File(config_path));
+    }
+
+    let mut config: Config = builder
+        .set_override_option("context", context)?
         .build()?
         .try_deserialize()?;
+    config.origins = origins;
+    config.dry_run = dry_run;
+    config.raw_body = raw_body;
+    config.print_curl = print_curl;
+    config.verbosity = verbosity;
+    config.record = record;
+    config.replay = replay;
+
+    Ok(config)
+}
 
+fn file_layer(path: &Path) -> Result<config::Config> {
+    let config = config::Config::builder()
+        .add_source(config::File::from(path).required(false))
+        .build()?;
     Ok(config)
 }
 
-/// Return the path to the jwctl configuration file
-pub fn config_file() -> Result<PathBuf> {
+/// Load a project configuration file. Unless its directory is trusted, keys
+/// other than those in `PROJECT_KEYS` are ignored with a warning, as the
+/// file may come from a repository checked out from anywhere.
+fn project_layer(path: &Path, trusted: bool) -> Result<config::Config> {
+    debug!("Loading project configuration from {:?}", path);
+    let layer = file_layer(path)?;
+    if trusted {
+        return Ok(layer);
+    }
+
+    let (values, ignored): (config::Map<_, _>, config::Map<_, _>) = layer
+        .collect()?
+        .into_iter()
+        .partition(|(key, _)| PROJECT_KEYS.contains(&key.as_str()));
+    if !ignored.is_empty() {
+        let mut keys: Vec<&str> = ignored.keys().map(String::as_str).collect();
+        keys.sort();
+        warn!(
+            "Ignoring {} from {path:?}. Add its directory to trusted_projects to allow them",
+            keys.join(", ")
+        );
+    }
+    layer_from(values)
+}
+
+/// Whether the directory of a project configuration file is listed in the
+/// user's `trusted_projects`
+fn is_trusted(user_config: &config::Config, path: &Path) -> Result<bool> {
+    #[derive(Deserialize)]
+    struct Trust {
+        #[serde(default, deserialize_with = "string_list")]
+        trusted_projects: Vec<String>,
+    }
+
+    let trust: Trust = user_config.clone().try_deserialize()?;
+    let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let Some(dir) = path.parent().map(canonical) else {
+        return Ok(false);
+    };
+    Ok(trust
+        .trusted_projects
+        .iter()
+        .any(|trusted| canonical(Path::new(trusted)) == dir))
+}
+
+/// Build a configuration layer from a map of values
+fn layer_from(values: config::Map<String, config::Value>) -> Result<config::Config> {
+    let mut builder = config::Config::builder();
+    for (key, value) in values {
+        builder = builder.set_override(key, value)?;
+    }
+    Ok(builder.build()?)
+}
+
+/// Search for a project configuration file in the current directory and
+/// its parents, stopping at the first one found.
+fn find_project_file() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Return the dotted paths of all values in a configuration map
+fn flatten_keys(map: config::Map<String, config::Value>, prefix: &str) -> Vec<String> {
+    map.into_iter()
+        .flat_map(|(key, value)| {
+            let key = format!("{prefix}{key}");
+            match value.into_table() {
+                Ok(table) => flatten_keys(table, &format!("{key}.")),
+                Err(_) => vec![key],
+            }
+        })
+        .collect()
+}
+
+/// Determine which context to use. An explicitly requested context takes
+/// precedence over the `current_context` stored in the configuration file.
+fn selected_context(base: &config::Config) -> Result<Option<String>> {
+    match get_optional(base, "context")? {
+        Some(context) => Ok(Some(context)),
+        None => get_optional(base, "current_context"),
+    }
+}
+
+fn get_optional<T: DeserializeOwned>(config: &config::Config, key: &str) -> Result<Option<T>> {
+    match config.get(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(config::ConfigError::NotFound(_)) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Build a configuration layer from the values of a named context.
+fn context_config(base: &config::Config, context: Option<&str>) -> Result<config::Config> {
+    let mut builder = config::Config::builder();
+
+    if let Some(name) = context {
+        let contexts = base.get_table("contexts").unwrap_or_default();
+        let values = contexts
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::msg(format!("Context {name} is not defined")))?
+            .into_table()?;
+
+        for (key, value) in values {
+            builder = builder.set_override(key, value)?;
+        }
+    }
+
+    Ok(builder.build()?)
+}
+
+/// List the contexts defined in the configuration file, marking the one
+/// that would be used with the given overrides.
+pub fn list_contexts(args: Overrides) -> Result<Vec<ContextInfo>> {
+    let file_config = file_layer(&config_file(args.config.as_deref())?)?;
+    let env_config = config::Config::builder()
+        .add_source(config::Environment::with_prefix("JW"))
+        .add_source(args)
+        .build()?;
+
+    let mut builder = config::Config::builder().add_source(file_config.clone());
+    if let Some(path) = find_project_file() {
+        let user_config = config::Config::builder()
+            .add_source(file_config)
+            .add_source(env_config.clone())
+            .build()?;
+        builder = builder.add_source(project_layer(&path, is_trusted(&user_config, &path)?)?);
+    }
+
+    let base = builder.add_source(env_config).build()?;
+    let current = selected_context(&base)?;
+
+    let contexts = base.get_table("contexts").unwrap_or_default();
+    let mut contexts: Vec<ContextInfo> = contexts
+        .into_iter()
+        .map(|(name, value)| {
+            let url = value
+                .into_table()
+                .ok()
+                .and_then(|values| values.get("url").cloned())
+                .and_then(|url| url.into_string().ok());
+            let current = current.as_ref() == Some(&name);
+            ContextInfo { name, url, current }
+        })
+        .collect();
+    contexts.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(contexts)
+}
+
+/// Persist the selected context as `current_context` in the configuration file.
+pub fn use_context(path: &Path, name: &str) -> Result<()> {
+    set_value(path, "current_context", name)
+}
+
+fn ensure_context_defined(path: &Path, name: &str) -> Result<()> {
+    let defined = file_layer(path)?
+        .get_table("contexts")
+        .unwrap_or_default()
+        .contains_key(name);
+    if !defined {
+        return Err(Error::msg(format!(
+            "Context {name} is not defined in {path:?}"
+        )));
+    }
+    Ok(())
+}
+
+/// Set a single key in the configuration file. Nested keys are separated
+/// by dots, eg `contexts.staging.url`.
+pub fn set_value(path: &Path, key: &str, value: &str) -> Result<()> {
+    let segments: Vec<&str> = key.split('.').collect();
+    if segments == ["current_context"] {
+        ensure_context_defined(path, value)?;
+    }
+    let value = validate_setting(&segments, value)?;
+
+    let contents = read_config_file(path)?;
+    let contents = yaml_edit::set(&contents, &segments, &value)?;
+    write_config_file(path, &contents)
+}
+
+/// Remove a single key from the configuration file.
+pub fn unset_value(path: &Path, key: &str) -> Result<()> {
+    let segments: Vec<&str> = key.split('.').collect();
+
+    let contents = read_config_file(path)?;
+    let contents = yaml_edit::unset(&contents, &segments)?
+        .ok_or_else(|| Error::msg(format!("Key {key} is not set in {path:?}")))?;
+    write_config_file(path, &contents)
+}
+
+/// Return the contents of the configuration file, or of a single key in it.
+/// The user and password of URLs are redacted unless `show_secrets` is set.
+pub fn view(path: &Path, key: Option<&str>, show_secrets: bool) -> Result<String> {
+    let contents = read_config_file(path)?;
+
+    let view = match key {
+        None => contents,
+        Some(key) => {
+            let doc: serde_yaml::Value = serde_yaml::from_str(&contents)?;
+            let value = key
+                .split('.')
+                .try_fold(&doc, |value, segment| value.get(segment))
+                .ok_or_else(|| Error::msg(format!("Key {key} is not set in {path:?}")))?;
+            serde_yaml::to_string(value)?
+        }
+    };
+    match show_secrets {
+        true => Ok(view),
+        false => Ok(redact::userinfo(&view)),
+    }
+}
+
+/// Check that a value is valid for the given key, returning it formatted
+/// as a YAML scalar.
+fn validate_setting(key: &[&str], value: &str) -> Result<String> {
+    let setting = match key {
+        ["current_context"] | ["trusted_projects"] => return Ok(yaml_edit::scalar(value)),
+        ["headers", _] | ["contexts", _, "headers", _] => return Ok(yaml_edit::scalar(value)),
+        [setting] | ["contexts", _, setting] => *setting,
+        _ => {
+            return Err(Error::msg(format!(
+                "Unknown configuration key {}",
+                key.join(".")
+            )))
+        }
+    };
+
+    match setting {
+        "url" => {
+            url::Url::parse(value)
+                .map_err(|err| Error::msg(format!("Invalid URL {value}: {err}")))?;
+        }
+        "token" | "credential_helper" | "ca_cert" | "client_cert" | "client_key" => (),
+        "retries" => {
+            let value = value
+                .parse::<u32>()
+                .map_err(|_| Error::msg("retries must be a number"))?;
+            return Ok(value.to_string());
+        }
+        "connect_timeout" | "timeout" => {
+            let value = value
+                .parse::<u64>()
+                .map_err(|_| Error::msg(format!("{setting} must be a number of seconds")))?;
+            return Ok(value.to_string());
+        }
+        "proxy" => {
+            url::Url::parse(value)
+                .map_err(|err| Error::msg(format!("Invalid proxy URL {value}: {err}")))?;
+        }
+        "no_proxy" => (),
+        "headers" => {
+            parse_headers(value)?;
+        }
+        "insecure_skip_verify" => {
+            let value = value
+                .parse::<bool>()
+                .map_err(|_| Error::msg("insecure_skip_verify must be true or false"))?;
+            return Ok(value.to_string());
+        }
+        "spki_pins" => {
+            for pin in value.split(',') {
+                tls::parse_pin(pin)?;
+            }
+        }
+        "credential_store" => {
+            serde_yaml::from_str::<StoreKind>(value).map_err(|_| {
+                Error::msg("credential_store must be one of file, encrypted-file or helper")
+            })?;
+        }
+        _ => {
+            return Err(Error::msg(format!(
+                "Unknown configuration key {}",
+                key.join(".")
+            )))
+        }
+    };
+
+    Ok(yaml_edit::scalar(value))
+}
+
+fn read_config_file(path: &Path) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(contents),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Atomically replace the configuration file, making sure the new contents
+/// are still valid YAML.
+fn write_config_file(path: &Path, contents: &str) -> Result<()> {
+    serde_yaml::from_str::<serde_yaml::Value>(contents)
+        .map_err(|err| Error::msg(format!("Refusing to write invalid configuration: {err}")))?;
+
+    let dir = path
+        .parent()
+        .ok_or_else(|| Error::msg("Invalid configuration file path"))?;
+    // The directory of a file passed with `--config` is left as it is, as
+    // it may be shared with other files
+    if dir == config_dir()? {
+        permissions::create_dir(dir)?;
+    } else {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut tmp_path = path.to_path_buf();
+    tmp_path.set_extension("yaml.tmp");
+    debug!("Saving configuration to {:?}", path);
+    permissions::write_file(&tmp_path, contents)?;
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+/// Return the path to the jwctl configuration file, unless a custom one
+/// was requested.
+pub fn config_file(custom: Option<&Path>) -> Result<PathBuf> {
+    if let Some(path) = custom {
+        return Ok(path.to_path_buf());
+    }
+
     let mut path = config_dir()?;
     path.push(CONFIG_FILE);
     Ok(path)
 }
 
-fn config_dir() -> Result<PathBuf> {
-    let mut path = home::home_dir().ok_or(Error::msg("Unable to find home dir!"))?;
-    path.push(".config");
+/// Return the directory holding jwctl configuration and state. In order of
+/// preference this is `$JW_CONFIG_DIR`, `$XDG_CONFIG_HOME/jwctl` or
+/// `~/.config/jwctl`.
+pub fn config_dir() -> Result<PathBuf> {
+    if let Some(dir) = env::var_os("JW_CONFIG_DIR").filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+
+    // Relative paths are invalid according to the XDG spec and are ignored
+    let mut path = match env::var_os("XDG_CONFIG_HOME").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => {
+            let mut home = home::home_dir().ok_or(Error::msg("Unable to find home dir!"))?;
+            home.push(".config");
+            home
+        }
+    };
     path.push("jwctl");
     Ok(path)
 }
 
-/// Store an authentication token into a local file.
-pub fn save_token(token: String) -> Result<()> {
+/// Store an authentication token in the configured credential store.
+pub fn save_token(config: &Config, token: String) -> Result<()> {
+    config.credential_store()?.store(&token)
+}
+
+/// Remove the authentication token from the configured credential store.
+pub fn erase_token(config: &Config) -> Result<()> {
+    config.credential_store()?.erase()
+}
+
+/// Return the directory holding the cookies and token for a gateway. The
+/// directory is named after the context when one is selected, or after the
+/// gateway's host and port otherwise, so that sessions for different
+/// gateways are kept apart.
+pub fn gateway_dir(context: Option<&str>, url: Option<&url::Url>) -> Result<PathBuf> {
     let mut path = config_dir()?;
-    fs::create_dir_all(&path)?;
+    match (context, url) {
+        (Some(context), _) => {
+            path.push(CONTEXTS_DIR);
+            path.push(context);
+        }
+        (None, Some(url)) => {
+            path.push(GATEWAYS_DIR);
+            path.push(gateway_key(url));
+        }
+        (None, None) => (),
+    }
+    Ok(path)
+}
 
-    path.push(TOKEN_FILE);
-    info!("Saving token to {:?}", path);
-    fs::write(path, token)?;
+/// Move the token and cookies that earlier versions kept directly in the
+/// configuration directory into the directory of the gateway they were
+/// saved for, which is the top-level `url` of the configuration file. They
+/// are never used for any other gateway.
+fn migrate_legacy_files(url: Option<&url::Url>) -> Result<()> {
+    let dir = config_dir()?;
+    for name in LEGACY_FILES {
+        let legacy = dir.join(name);
+        if !legacy.is_file() {
+            continue;
+        }
+
+        let Some(url) = url else {
+            warn!("Ignoring {legacy:?} from an earlier version, as no url is configured to move it to");
+            continue;
+        };
+        let gateway = gateway_dir(None, Some(url))?;
+        let target = gateway.join(name);
+        if target.exists() {
+            warn!("Ignoring {legacy:?} from an earlier version, as {target:?} already exists");
+            continue;
+        }
+
+        info!("Moving {legacy:?} to {target:?}");
+        permissions::create_dir(&gateway)?;
+        fs::rename(&legacy, &target)?;
+    }
     Ok(())
 }
 
-/// Load an existing set of cookies, serialized as json
-pub fn get_cookie_store() -> Result<Arc<CookieStoreMutex>> {
-    let mut path = config_dir()?;
-    path.push("cookies.json");
+/// Build a directory name identifying a gateway, eg `localhost_4004`
+fn gateway_key(url: &url::Url) -> String {
+    let mut key = url.host_str().unwrap_or("default").to_string();
+    if let Some(port) = url.port_or_known_default() {
+        key.push_str(&format!("_{port}"));
+    }
+    let path = url.path().trim_matches('/');
+    if !path.is_empty() {
+        key.push_str(&format!("_{path}"));
+    }
+
+    key.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Return the path of the cookie jar for the configured gateway.
+pub fn cookie_file(config: &Config) -> Result<PathBuf> {
+    let mut path = config.gateway_dir()?;
+    path.push(COOKIE_FILE);
+    Ok(path)
+}
+
+/// Load an existing set of cookies, serialized as json.
+pub fn get_cookie_store(config: &Config) -> Result<Arc<CookieStoreMutex>> {
+    let path = cookie_file(config)?;
     debug!("Loading cookies from {:?}", path);
+    permissions::warn_if_loose(&path);
 
     let store = match fs::File::open(path).map(std::io::BufReader::new) {
         Ok(file) => CookieStore::load_json_all(file)
@@ -90,13 +1005,14 @@ pub fn get_cookie_store() -> Result<Arc<CookieStoreMutex>> {
 }
 
 /// Write reqwest cookies back to disk
-pub fn save_cookies(cookie_store: Arc<CookieStoreMutex>) -> Result<()> {
-    let mut path = config_dir()?;
-    fs::create_dir_all(&path)?;
-    path.push("cookies.json");
+pub fn save_cookies(config: &Config, cookie_store: Arc<CookieStoreMutex>) -> Result<()> {
+    let path = cookie_file(config)?;
+    if let Some(dir) = path.parent() {
+        permissions::create_dir(dir)?;
+    }
     debug!("Saving cookies to {:?}", path);
 
-    let mut writer = std::fs::File::create(path).map(std::io::BufWriter::new)?;
+    let mut writer = permissions::create_file(&path).map(std::io::BufWriter::new)?;
     let store = cookie_store
         .lock()
         .map_err(|_| Error::msg("Could not lock the cookie store to save cookies"))?;
@@ -0,0 +1,369 @@
+//! Storage backends for the authentication token.
+//!
+//! The backend is selected with the `credential_store` configuration key:
+//! - `file` stores the token in plaintext in the configuration directory
+//! - `encrypted-file` encrypts the token with a passphrase
+//! - `helper` delegates to an external program speaking the git credential
+//!   helper protocol, configured with `credential_helper`
+
+use std::fs;
+use std::io::Write;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use crate::permissions;
+
+use anyhow::{Error, Result};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{digest, pbkdf2};
+use serde::{Deserialize, Serialize};
+
+pub(crate) const TOKEN_FILE: &str = ".token";
+pub(crate) const ENCRYPTED_TOKEN_FILE: &str = ".token.enc";
+const PASSPHRASE_ENV: &str = "JW_CREDENTIAL_PASSPHRASE";
+
+/// Asks for the passphrase of the encrypted token, confirming it when the
+/// argument is true, as when a new token is stored
+pub type PassphrasePrompt = fn(confirm: bool) -> Result<String>;
+
+static PASSPHRASE_PROMPT: OnceLock<PassphrasePrompt> = OnceLock::new();
+
+/// Set how to ask for the passphrase of the encrypted token when
+/// `JW_CREDENTIAL_PASSPHRASE` isn't set. Only the first prompt set is used.
+pub fn set_passphrase_prompt(prompt: PassphrasePrompt) {
+    let _ = PASSPHRASE_PROMPT.set(prompt);
+}
+
+const ENCRYPTED_MAGIC: &[u8] = b"JWCTL1";
+const SALT_LEN: usize = 16;
+const PBKDF2_ITERATIONS: u32 = 600_000;
+
+/// The available credential storage backends
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum StoreKind {
+    #[default]
+    File,
+    EncryptedFile,
+    Helper,
+}
+
+/// A place where the authentication token can be kept between invocations
+pub trait CredentialStore {
+    /// Retrieve the stored token, if there is one
+    fn get(&self) -> Result<Option<String>>;
+
+    /// Store a token, replacing any existing one
+    fn store(&self, token: &str) -> Result<()>;
+
+    /// Remove the stored token
+    fn erase(&self) -> Result<()>;
+
+    /// Human readable description of where tokens are stored
+    fn describe(&self) -> String;
+
+    /// Local file holding the token, if any
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// Open the credential store of the given kind. File based stores keep
+/// their token in the gateway's directory, while the gateway URL is passed
+/// along to credential helpers so they can store tokens per gateway.
+pub fn open(
+    kind: &StoreKind,
+    helper: Option<&str>,
+    url: Option<&url::Url>,
+    gateway_dir: &Path,
+) -> Result<Box<dyn CredentialStore>> {
+    let store: Box<dyn CredentialStore> = match kind {
+        StoreKind::File => Box::new(FileStore {
+            path: gateway_dir.join(TOKEN_FILE),
+        }),
+        StoreKind::EncryptedFile => Box::new(EncryptedFileStore {
+            path: gateway_dir.join(ENCRYPTED_TOKEN_FILE),
+        }),
+        StoreKind::Helper => {
+            let command = helper.ok_or_else(|| {
+                Error::msg("credential_helper must be set to use the helper credential store")
+            })?;
+            Box::new(HelperStore {
+                command: command.to_string(),
+                url: url.cloned(),
+            })
+        }
+    };
+
+    Ok(store)
+}
+
+/// Plaintext token file
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl CredentialStore for FileStore {
+    fn get(&self) -> Result<Option<String>> {
+        permissions::warn_if_loose(&self.path);
+        Ok(fs::read_to_string(&self.path).ok())
+    }
+
+    fn store(&self, token: &str) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            permissions::create_dir(dir)?;
+        }
+
+        debug!("Saving token to {:?}", self.path);
+        permissions::write_file(&self.path, token)?;
+        Ok(())
+    }
+
+    fn erase(&self) -> Result<()> {
+        remove_if_exists(&self.path)
+    }
+
+    fn describe(&self) -> String {
+        format!("file {}", self.path.display())
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+/// Token file encrypted with ChaCha20-Poly1305, using a key derived from a
+/// passphrase with PBKDF2. The passphrase is read from
+/// `JW_CREDENTIAL_PASSPHRASE`, or asked for with the prompt set with
+/// `set_passphrase_prompt`.
+pub struct EncryptedFileStore {
+    path: PathBuf,
+}
+
+impl EncryptedFileStore {
+    fn key(passphrase: &str, salt: &[u8]) -> Result<LessSafeKey> {
+        let mut key = [0; digest::SHA256_OUTPUT_LEN];
+        let iterations = NonZeroU32::new(PBKDF2_ITERATIONS).expect("iterations are non-zero");
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            iterations,
+            salt,
+            passphrase.as_bytes(),
+            &mut key,
+        );
+
+        let key = UnboundKey::new(&CHACHA20_POLY1305, &key)
+            .map_err(|_| Error::msg("Failed to create encryption key"))?;
+        Ok(LessSafeKey::new(key))
+    }
+
+    fn passphrase(confirm: bool) -> Result<String> {
+        if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+            return Ok(passphrase);
+        }
+
+        match PASSPHRASE_PROMPT.get() {
+            Some(prompt) => prompt(confirm),
+            None => Err(Error::msg(format!(
+                "{PASSPHRASE_ENV} must be set to use the encrypted token"
+            ))),
+        }
+    }
+}
+
+impl CredentialStore for EncryptedFileStore {
+    fn get(&self) -> Result<Option<String>> {
+        let path = &self.path;
+        permissions::warn_if_loose(path);
+        let contents = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(_) => return Ok(None),
+        };
+
+        let header_len = ENCRYPTED_MAGIC.len() + SALT_LEN + NONCE_LEN;
+        if contents.len() < header_len || !contents.starts_with(ENCRYPTED_MAGIC) {
+            return Err(Error::msg(format!(
+                "{path:?} is not a valid encrypted token file"
+            )));
+        }
+
+        let (salt, rest) = contents[ENCRYPTED_MAGIC.len()..].split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce)
+            .map_err(|_| Error::msg("Invalid nonce in encrypted token file"))?;
+
+        let key = Self::key(&Self::passphrase(false)?, salt)?;
+        let mut in_out = ciphertext.to_vec();
+        let token = key
+            .open_in_place(nonce, Aad::empty(), &mut in_out)
+            .map_err(|_| Error::msg("Failed to decrypt the token, is the passphrase correct?"))?;
+
+        Ok(Some(String::from_utf8(token.to_vec())?))
+    }
+
+    fn store(&self, token: &str) -> Result<()> {
+        let rng = SystemRandom::new();
+        let mut salt = [0; SALT_LEN];
+        let mut nonce = [0; NONCE_LEN];
+        rng.fill(&mut salt)
+            .and_then(|_| rng.fill(&mut nonce))
+            .map_err(|_| Error::msg("Failed to generate random data"))?;
+
+        let key = Self::key(&Self::passphrase(true)?, &salt)?;
+        let mut in_out = token.as_bytes().to_vec();
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::empty(),
+            &mut in_out,
+        )
+        .map_err(|_| Error::msg("Failed to encrypt the token"))?;
+
+        let mut contents = ENCRYPTED_MAGIC.to_vec();
+        contents.extend_from_slice(&salt);
+        contents.extend_from_slice(&nonce);
+        contents.extend_from_slice(&in_out);
+
+        if let Some(dir) = self.path.parent() {
+            permissions::create_dir(dir)?;
+        }
+
+        debug!("Saving encrypted token to {:?}", self.path);
+        permissions::write_file(&self.path, contents)?;
+        Ok(())
+    }
+
+    fn erase(&self) -> Result<()> {
+        remove_if_exists(&self.path)
+    }
+
+    fn describe(&self) -> String {
+        format!("encrypted file {}", self.path.display())
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+/// External program implementing the git credential helper protocol. The
+/// helper is called with `get`, `store` or `erase` as its last argument and
+/// exchanges `key=value` lines over stdin and stdout. The token is passed
+/// as the `password` attribute, so existing git credential helpers can be
+/// used as-is.
+pub struct HelperStore {
+    command: String,
+    url: Option<url::Url>,
+}
+
+impl HelperStore {
+    fn run(&self, action: &str, token: Option<&str>) -> Result<String> {
+        let mut parts = self.command.split_whitespace();
+        let program = parts
+            .next()
+            .ok_or_else(|| Error::msg("credential_helper is empty"))?;
+
+        debug!("Running credential helper {} {}", self.command, action);
+        let mut child = Command::new(program)
+            .args(parts)
+            .arg(action)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| {
+                Error::msg(format!("Failed to run credential helper {program}: {err}"))
+            })?;
+
+        let mut input = self.attributes();
+        if let Some(token) = token {
+            input.push_str(&format!("password={token}\n"));
+        }
+        input.push('\n');
+
+        // Like git, a helper that exits without reading its input is judged
+        // by its exit status alone
+        let written = child
+            .stdin
+            .take()
+            .ok_or_else(|| Error::msg("Could not write to the credential helper"))?
+            .write_all(input.as_bytes());
+        match written {
+            Err(err) if err.kind() != std::io::ErrorKind::BrokenPipe => return Err(err.into()),
+            _ => (),
+        }
+
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(Error::msg(format!(
+                "Credential helper {program} {action} failed with {}",
+                output.status
+            )));
+        }
+
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// Attributes identifying the gateway, formatted for the helper protocol
+    fn attributes(&self) -> String {
+        let mut attrs = String::new();
+        if let Some(url) = &self.url {
+            attrs.push_str(&format!("protocol={}\n", url.scheme()));
+            if let Some(host) = url.host_str() {
+                match url.port() {
+                    Some(port) => attrs.push_str(&format!("host={host}:{port}\n")),
+                    None => attrs.push_str(&format!("host={host}\n")),
+                }
+            }
+            let path = url.path().trim_matches('/');
+            if !path.is_empty() {
+                attrs.push_str(&format!("path={path}\n"));
+            }
+        }
+        attrs.push_str("username=jwctl\n");
+        attrs
+    }
+}
+
+impl CredentialStore for HelperStore {
+    fn get(&self) -> Result<Option<String>> {
+        if self.url.is_none() {
+            return Ok(None);
+        }
+
+        let output = self.run("get", None)?;
+        let token = output
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .find(|(key, _)| *key == "password")
+            .map(|(_, value)| value.to_string());
+        Ok(token)
+    }
+
+    fn store(&self, token: &str) -> Result<()> {
+        if self.url.is_none() {
+            return Err(Error::msg("A gateway URL is required to store a token"));
+        }
+
+        self.run("store", Some(token))?;
+        debug!("Token stored with credential helper {}", self.command);
+        Ok(())
+    }
+
+    fn erase(&self) -> Result<()> {
+        self.run("erase", None).map(|_| ())
+    }
+
+    fn describe(&self) -> String {
+        format!("credential helper {}", self.command)
+    }
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
@@ -0,0 +1,200 @@
+use std::path::{Path, PathBuf};
+
+use crate::output::Format;
+use crate::Args;
+use jwctl::command;
+use jwctl::config::{self, Config};
+use jwctl::errors::ApiError;
+use jwctl::http::ApiClient;
+
+use anyhow::{Error, Result};
+use serde::Serialize;
+use strum_macros::Display;
+
+#[derive(Display, PartialEq, Eq, Serialize)]
+#[strum(serialize_all = "UPPERCASE")]
+#[serde(rename_all = "UPPERCASE")]
+enum Status {
+    Pass,
+    Fail,
+    Skip,
+}
+
+#[derive(Serialize)]
+struct Check {
+    #[serde(rename = "check")]
+    name: &'static str,
+    status: Status,
+    detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: Status, detail: impl Into<String>) -> Check {
+        Check {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Check the local configuration and connectivity to the gateway, printing
+/// a report. Returns an error if any check failed.
+pub fn run(args: &Args) -> Result<()> {
+    let mut checks = vec![];
+
+    let config = match config::load_config(args.overrides()) {
+        Ok(config) => {
+            let detail = format!("loaded configuration for {}", config.url);
+            checks.push(Check::new("configuration", Status::Pass, detail));
+            Some(config)
+        }
+        Err(err) => {
+            checks.push(Check::new("configuration", Status::Fail, err.to_string()));
+            None
+        }
+    };
+
+    let config_file = config::config_file(args.config.as_deref())?;
+    checks.push(check_permissions(&config_file, config.as_ref())?);
+
+    match config.as_ref().map(ApiClient::new) {
+        Some(Ok(api)) => {
+            checks.push(check_ping(&api));
+            checks.push(check_token(&api));
+            checks.push(check_sso(&api)?);
+        }
+        Some(Err(err)) => {
+            checks.push(Check::new("gateway", Status::Fail, err.to_string()));
+            for name in ["token", "sso session"] {
+                checks.push(Check::new(name, Status::Skip, "no HTTP client"));
+            }
+        }
+        None => {
+            for name in ["gateway", "token", "sso session"] {
+                checks.push(Check::new(name, Status::Skip, "no valid configuration"));
+            }
+        }
+    }
+
+    match args.structured_output() {
+        true => crate::print_result(args, &checks, Format::Table)?,
+        false => {
+            for check in &checks {
+                println!("[{}] {}: {}", check.status, check.name, check.detail);
+            }
+        }
+    }
+
+    let failed = checks.iter().filter(|c| c.status == Status::Fail).count();
+    match failed {
+        0 => Ok(()),
+        1 => Err(Error::msg("1 check failed")),
+        n => Err(Error::msg(format!("{n} checks failed"))),
+    }
+}
+
+fn check_permissions(config_file: &Path, config: Option<&Config>) -> Result<Check> {
+    let mut paths: Vec<PathBuf> = vec![config::config_dir()?, config_file.to_path_buf()];
+    if let Some(config) = config {
+        if let Some(path) = config.credential_store()?.path() {
+            paths.push(path.to_path_buf());
+        }
+        paths.push(config::cookie_file(config)?);
+    }
+
+    let loose: Vec<String> = paths
+        .iter()
+        .filter_map(|path| {
+            loose_mode(path).map(|mode| {
+                format!(
+                    "{} is accessible by other users (mode {:o})",
+                    path.display(),
+                    mode
+                )
+            })
+        })
+        .collect();
+
+    let check = if loose.is_empty() {
+        Check::new(
+            "permissions",
+            Status::Pass,
+            "files are only accessible by the current user",
+        )
+    } else {
+        Check::new("permissions", Status::Fail, loose.join(", "))
+    };
+    Ok(check)
+}
+
+/// The permission bits of a file or directory that other users can access,
+/// or `None` if it is private or does not exist
+fn loose_mode(path: &Path) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = std::fs::metadata(path).ok()?.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            return Some(mode);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+
+    None
+}
+
+fn check_ping(api: &ApiClient) -> Check {
+    let config = api.config();
+    match command::ping(api) {
+        Ok(resp) => Check::new(
+            "gateway",
+            Status::Pass,
+            format!("{} responded: {}", config.url, resp.trim()),
+        ),
+        Err(err) => Check::new(
+            "gateway",
+            Status::Fail,
+            format!("{} is unreachable: {:#}", config.url, err),
+        ),
+    }
+}
+
+fn check_token(api: &ApiClient) -> Check {
+    if api.config().token.is_none() {
+        return Check::new("token", Status::Skip, "no token configured");
+    }
+
+    match command::token_whoami(api) {
+        Ok(_) => Check::new("token", Status::Pass, "token is valid"),
+        Err(err) if err.is::<ApiError>() => Check::new(
+            "token",
+            Status::Fail,
+            format!("token was rejected: {err:#}"),
+        ),
+        Err(err) => Check::new("token", Status::Fail, format!("{err:#}")),
+    }
+}
+
+fn check_sso(api: &ApiClient) -> Result<Check> {
+    if !config::cookie_file(api.config())?.exists() {
+        return Ok(Check::new(
+            "sso session",
+            Status::Skip,
+            "not logged in with SSO",
+        ));
+    }
+
+    let check = match command::sso_whoami(api) {
+        Ok(_) => Check::new("sso session", Status::Pass, "SSO session is valid"),
+        Err(err) if err.is::<ApiError>() => Check::new(
+            "sso session",
+            Status::Fail,
+            format!("SSO session is not valid: {err:#}"),
+        ),
+        Err(err) => Check::new("sso session", Status::Fail, format!("{err:#}")),
+    };
+    Ok(check)
+}
@@ -0,0 +1,207 @@
+//! Errors reported to the user, and the exit code used for each kind.
+
+use std::fmt;
+
+use reqwest::{Method, StatusCode};
+
+/// Exit codes returned by jwctl, documented in the README
+pub const EXIT_ERROR: u8 = 1;
+pub const EXIT_CONFIG: u8 = 3;
+pub const EXIT_AUTH: u8 = 4;
+pub const EXIT_NOT_FOUND: u8 = 5;
+pub const EXIT_CONFLICT: u8 = 6;
+pub const EXIT_SERVER: u8 = 7;
+pub const EXIT_NETWORK: u8 = 8;
+
+/// An error reported by the gateway. The server's `error` message is
+/// included when the response has one.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ApiError {
+    /// 401, the token or SSO session is missing or invalid
+    Unauthorized { message: Option<String> },
+    /// 403, the token doesn't have the permission needed
+    Forbidden {
+        permission: String,
+        message: Option<String>,
+    },
+    /// 404
+    NotFound {
+        resource: String,
+        message: Option<String>,
+    },
+    /// 409
+    Conflict { message: Option<String> },
+    /// 5xx
+    Server {
+        status: StatusCode,
+        message: Option<String>,
+    },
+    /// Any other unsuccessful status
+    Failed {
+        status: StatusCode,
+        message: Option<String>,
+    },
+    /// A successful response with an `error` key
+    Rejected { message: String },
+    /// A successful response that isn't JSON
+    InvalidResponse { content_type: String },
+}
+
+impl ApiError {
+    /// Classify an unsuccessful response to a request
+    pub fn from_status(
+        status: StatusCode,
+        method: &Method,
+        path: &str,
+        message: Option<String>,
+    ) -> ApiError {
+        match status {
+            StatusCode::UNAUTHORIZED => ApiError::Unauthorized { message },
+            StatusCode::FORBIDDEN => ApiError::Forbidden {
+                permission: permission(method, path),
+                message,
+            },
+            StatusCode::NOT_FOUND => ApiError::NotFound {
+                resource: resource(path),
+                message,
+            },
+            StatusCode::CONFLICT => ApiError::Conflict { message },
+            status if status.is_server_error() => ApiError::Server { status, message },
+            status => ApiError::Failed { status, message },
+        }
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ApiError::Unauthorized { .. } | ApiError::Forbidden { .. } => EXIT_AUTH,
+            ApiError::NotFound { .. } => EXIT_NOT_FOUND,
+            ApiError::Conflict { .. } => EXIT_CONFLICT,
+            ApiError::Server { .. } | ApiError::InvalidResponse { .. } => EXIT_SERVER,
+            ApiError::Failed { .. } | ApiError::Rejected { .. } => EXIT_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            ApiError::Unauthorized { message } => {
+                write!(f, "not authenticated, check the token or log in again")?;
+                message
+            }
+            ApiError::Forbidden {
+                permission,
+                message,
+            } => {
+                write!(f, "token lacks {permission}")?;
+                message
+            }
+            ApiError::NotFound { resource, message } => {
+                write!(f, "{resource} not found")?;
+                message
+            }
+            ApiError::Conflict { message } => {
+                write!(f, "request conflicts with the gateway's current state")?;
+                message
+            }
+            ApiError::Server { status, message } => {
+                write!(f, "gateway failed with {status}")?;
+                message
+            }
+            ApiError::Failed { status, message } => {
+                write!(f, "request failed with {status}")?;
+                message
+            }
+            ApiError::Rejected { message } => return write!(f, "{message}"),
+            ApiError::InvalidResponse { content_type } => {
+                return write!(f, "expected a JSON response but got {content_type}")
+            }
+        };
+
+        match message {
+            Some(message) => write!(f, ": {message}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// The configuration could not be loaded
+#[derive(Debug)]
+pub struct InvalidConfig;
+
+impl fmt::Display for InvalidConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration")
+    }
+}
+
+/// A request was not sent because of `--dry-run`
+#[derive(Debug)]
+pub struct DryRun;
+
+impl fmt::Display for DryRun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Dry run, the request was not sent")
+    }
+}
+
+impl std::error::Error for DryRun {}
+
+/// Return the exit code for an error, based on the first error in its
+/// chain that has a known category.
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    if err.downcast_ref::<InvalidConfig>().is_some() {
+        return EXIT_CONFIG;
+    }
+
+    err.chain()
+        .find_map(|cause| {
+            if let Some(err) = cause.downcast_ref::<ApiError>() {
+                return Some(err.exit_code());
+            }
+            match cause.downcast_ref::<reqwest::Error>() {
+                Some(err) if err.is_connect() || err.is_timeout() => Some(EXIT_NETWORK),
+                _ => None,
+            }
+        })
+        .unwrap_or(EXIT_ERROR)
+}
+
+/// API path segments after the version prefix, eg `["manifests", "123"]`
+fn api_segments(path: &str) -> Vec<&str> {
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    match segments.as_slice() {
+        ["api", _version, rest @ ..] => rest.to_vec(),
+        _ => segments,
+    }
+}
+
+/// The permission needed for a request, eg `get:manifests`
+fn permission(method: &Method, path: &str) -> String {
+    let resource = api_segments(path).first().copied().unwrap_or_default();
+    format!("{}:{}", method.as_str().to_lowercase(), resource)
+}
+
+/// Describe the resource a path refers to, eg `manifest 123` for
+/// `/api/v1/manifests/123`
+fn resource(path: &str) -> String {
+    let segments = api_segments(path);
+    match segments.len() {
+        0 => path.to_string(),
+        1 => segments[0].to_string(),
+        len => {
+            // Collections are followed by the ID of an item in them
+            let start = (len - 2) & !1;
+            let collection = segments[start];
+            let id = segments[start + 1];
+            format!(
+                "{} {}",
+                collection.strip_suffix('s').unwrap_or(collection),
+                id
+            )
+        }
+    }
+}
@@ -1,15 +1,494 @@
-use anyhow::Result;
-use reqwest::blocking::RequestBuilder;
-use std::sync::Arc;
+use crate::cassette::{Cassette, Mode};
+use crate::config::{get_cookie_store, save_cookies, Config};
+use crate::errors::{ApiError, DryRun};
+use crate::preview;
+use crate::redact;
+use crate::tls;
 
-pub fn client(
-    cookie_store: &Arc<reqwest_cookie_store::CookieStoreMutex>,
+use anyhow::{Context, Error, Result};
+use reqwest::blocking::{Request, RequestBuilder, Response};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, RETRY_AFTER, SET_COOKIE};
+use reqwest::{Method, NoProxy, Proxy, StatusCode};
+use reqwest_cookie_store::CookieStoreMutex;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Statuses indicating that the gateway is temporarily unavailable
+const RETRY_STATUSES: [StatusCode; 3] = [
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(10);
+const RETRY_AFTER_MAX: Duration = Duration::from_secs(60);
+
+/// Header identifying a request in the gateway's logs
+const REQUEST_ID: &str = "x-request-id";
+
+/// Shows the preview of a request made with `--dry-run` or `--print-curl`
+pub type PreviewPrinter = fn(preview: &str);
+
+static PREVIEW_PRINTER: OnceLock<PreviewPrinter> = OnceLock::new();
+
+/// Set how request previews are shown. Until one is set, previews are
+/// logged at the info level, which needs the `cli` feature. Only the first
+/// printer set is used.
+pub fn set_preview_printer(printer: PreviewPrinter) {
+    let _ = PREVIEW_PRINTER.set(printer);
+}
+
+/// Client for the JumpWire API. Every request is authenticated with the
+/// configured token and the gateway's cookies, which are saved again when
+/// the gateway sets new ones.
+pub struct ApiClient {
+    config: Config,
+    client: reqwest::blocking::Client,
+    cookie_store: Arc<CookieStoreMutex>,
+    cassette: Option<Mutex<Cassette>>,
+}
+
+impl ApiClient {
+    pub fn new(config: &Config) -> Result<ApiClient> {
+        let cookie_store = get_cookie_store(config)?;
+        let client = client(config, &cookie_store)?;
+        let cassette = match (&config.record, &config.replay) {
+            (_, Some(path)) => Some(Cassette::replay(path)?),
+            (Some(path), None) => Some(Cassette::record(path)?),
+            (None, None) => None,
+        };
+        Ok(ApiClient {
+            config: config.clone(),
+            client,
+            cookie_store,
+            cassette: cassette.map(Mutex::new),
+        })
+    }
+
+    /// The configuration used to build this client
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Return the URL of an API endpoint from the segments of its path, eg
+    /// `["api", "v1", "status"]`, joined onto the path of the gateway URL for
+    /// gateways mounted under a prefix like `https://infra.example.com/jumpwire/`.
+    /// Segments are escaped, so an ID can't change the rest of the URL.
+    pub fn endpoint(&self, segments: &[&str]) -> url::Url {
+        let mut url = self.config.url.clone();
+        url.set_query(None);
+        if let Ok(mut path) = url.path_segments_mut() {
+            path.pop_if_empty().extend(segments);
+        }
+        url
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, path: &[&str]) -> Result<T> {
+        self.send(Method::GET, self.endpoint(path), None::<&Value>)
+    }
+
+    pub fn post<T: DeserializeOwned>(&self, path: &[&str], body: &impl Serialize) -> Result<T> {
+        self.send(Method::POST, self.endpoint(path), Some(body))
+    }
+
+    pub fn put<T: DeserializeOwned>(
+        &self,
+        path: &[&str],
+        body: Option<&impl Serialize>,
+    ) -> Result<T> {
+        self.send(Method::PUT, self.endpoint(path), body)
+    }
+
+    pub fn delete<T: DeserializeOwned>(&self, path: &[&str]) -> Result<T> {
+        self.send(Method::DELETE, self.endpoint(path), None::<&Value>)
+    }
+
+    /// Make a request to any URL with any method, returning the response as is
+    pub fn request(
+        &self,
+        method: Method,
+        url: url::Url,
+        body: Option<&impl Serialize>,
+    ) -> Result<RawResponse> {
+        self.execute(method, url, body)
+    }
+
+    /// Make a GET request returning the response body as text
+    pub fn get_text(&self, path: &[&str]) -> Result<String> {
+        let resp = self.execute(Method::GET, self.endpoint(path), None::<&Value>)?;
+        Ok(String::from_utf8_lossy(&resp.body).to_string())
+    }
+
+    /// Send a request, turning an unsuccessful status into an `ApiError`.
+    /// Each request carries a generated `X-Request-ID`, which is included in
+    /// any error to correlate it with the gateway's logs.
+    fn execute(
+        &self,
+        method: Method,
+        url: url::Url,
+        body: Option<&impl Serialize>,
+    ) -> Result<RawResponse> {
+        let request_id = request_id();
+        let path = self.api_path(&url);
+        let mut request = self
+            .client
+            .request(method.clone(), url)
+            .header(REQUEST_ID, &request_id);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        let request = maybe_add_auth(request, self.config.token.clone()).build()?;
+        self.preview(&request)?;
+
+        self.execute_with_retries(request, &path)
+            .with_context(|| format!("{method} {path} failed (request ID {request_id})"))
+    }
+
+    /// Send a built request. Idempotent requests are retried when the
+    /// gateway can't be reached or is temporarily unavailable.
+    fn execute_with_retries(&self, request: Request, path: &str) -> Result<RawResponse> {
+        let method = request.method().clone();
+        let idempotent = matches!(method, Method::GET | Method::PUT | Method::DELETE);
+        let mut attempt = 0;
+
+        let resp = loop {
+            let request = request
+                .try_clone()
+                .ok_or_else(|| Error::msg("Request body can't be sent again"))?;
+            let result = self.send_traced(request);
+            let retry = idempotent && attempt < self.config.retries;
+            let (reason, delay) = match &result {
+                Err(err) if retry && is_connect_error(err) => {
+                    debug!("Request failed: {}", err);
+                    (
+                        "Could not connect to the gateway".to_string(),
+                        backoff(attempt),
+                    )
+                }
+                Ok(resp) if retry && RETRY_STATUSES.contains(&resp.status) => (
+                    format!("Gateway responded with {}", resp.status),
+                    retry_after(&resp.headers).unwrap_or_else(|| backoff(attempt)),
+                ),
+                _ => break result?,
+            };
+
+            attempt += 1;
+            warn!(
+                "{}, retrying {} {} in {:.1}s (attempt {} of {})",
+                reason,
+                method,
+                path,
+                delay.as_secs_f32(),
+                attempt,
+                self.config.retries
+            );
+            thread::sleep(delay);
+        };
+
+        // Replayed cookies aren't from the gateway, so the saved ones are kept
+        if resp.headers.contains_key(SET_COOKIE) && self.config.replay.is_none() {
+            save_cookies(&self.config, Arc::clone(&self.cookie_store))?;
+        }
+
+        let status = resp.status;
+        if status.is_success() {
+            return Ok(resp);
+        }
+
+        // Error responses usually explain what went wrong in an `error` key
+        let message = match resp.is_json() {
+            true => serde_json::from_slice::<Value>(&resp.body)
+                .ok()
+                .and_then(|body| body.get("error").map(error_message)),
+            false => None,
+        };
+        Err(ApiError::from_status(status, &method, path, message).into())
+    }
+
+    /// Send a request once and read the whole response. With `-vv` the
+    /// request and response are logged, and with `-vvv` their headers and
+    /// bodies too, with credentials redacted.
+    fn send_traced(&self, request: Request) -> Result<RawResponse> {
+        let verbosity = self.config.verbosity;
+        let (method, url) = (request.method().clone(), request.url().clone());
+
+        if verbosity >= 2 {
+            debug!("> {} {}", method, url);
+        }
+        if verbosity >= 3 {
+            for (name, value) in request.headers() {
+                debug!("> {}: {}", name, redact::header(name, value));
+            }
+            if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+                debug!("> {}", redact::body(body));
+            }
+        }
+
+        let started = Instant::now();
+        let result = self.send_once(request);
+        let elapsed = started.elapsed().as_millis();
+
+        match &result {
+            Ok(resp) if verbosity >= 2 => {
+                debug!(
+                    "< {} {} {} in {}ms, {} bytes",
+                    resp.status,
+                    method,
+                    url,
+                    elapsed,
+                    resp.body.len()
+                );
+                if verbosity >= 3 {
+                    for (name, value) in &resp.headers {
+                        debug!("< {}: {}", name, redact::header(name, value));
+                    }
+                    if !resp.body.is_empty() {
+                        debug!("< {}", redact::body(&resp.body));
+                    }
+                }
+            }
+            Err(err) if verbosity >= 2 => {
+                debug!("< {} {} failed after {}ms: {}", method, url, elapsed, err);
+            }
+            _ => (),
+        }
+
+        result
+    }
+
+    /// Send a request, or replay its response from a cassette. When
+    /// recording, the exchange is added to the cassette.
+    fn send_once(&self, request: Request) -> Result<RawResponse> {
+        let cassette = match &self.cassette {
+            Some(cassette) => cassette,
+            None => return Ok(self.client.execute(request).and_then(RawResponse::read)?),
+        };
+        let mut cassette = cassette
+            .lock()
+            .map_err(|_| Error::msg("Cassette is unavailable"))?;
+
+        match cassette.mode() {
+            Mode::Replay => cassette.find(&request),
+            Mode::Record => {
+                let recorded = request
+                    .try_clone()
+                    .ok_or_else(|| Error::msg("Request can't be recorded"))?;
+                let resp = self.client.execute(request).and_then(RawResponse::read)?;
+                cassette.add(&recorded, &resp)?;
+                Ok(resp)
+            }
+        }
+    }
+
+    /// Show a request with the preview printer when previewing with
+    /// `--dry-run` or `--print-curl`. With `--dry-run`, reads are sent as
+    /// usual so that commands get as far as their first change, which is
+    /// shown instead of being sent.
+    fn preview(&self, request: &Request) -> Result<()> {
+        let stop = self.config.dry_run && !matches!(*request.method(), Method::GET | Method::HEAD);
+        let preview = if self.config.print_curl {
+            Some(preview::curl(request, &self.config))
+        } else if stop {
+            let has_cookies = match self.cookie_store.lock() {
+                Ok(store) => store.get_request_values(request.url()).next().is_some(),
+                Err(_) => false,
+            };
+            Some(preview::describe(request, &self.config, has_cookies))
+        } else {
+            None
+        };
+
+        if let Some(preview) = preview {
+            match PREVIEW_PRINTER.get() {
+                Some(printer) => printer(&preview),
+                None => info!("{preview}"),
+            }
+        }
+
+        if stop {
+            return Err(DryRun.into());
+        }
+        Ok(())
+    }
+
+    /// The path of a URL after the gateway's path prefix, eg
+    /// `/api/v1/status`, used to describe requests in errors and logs
+    fn api_path(&self, url: &url::Url) -> String {
+        let base = self.config.url.path().trim_end_matches('/');
+        match url.path().strip_prefix(base) {
+            Some(path) if path.starts_with('/') => path.to_string(),
+            _ => url.path().to_string(),
+        }
+    }
+
+    /// Send a request and deserialize the JSON response, turning an `error`
+    /// key into an `ApiError`
+    fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: url::Url,
+        body: Option<&impl Serialize>,
+    ) -> Result<T> {
+        let resp = self.execute(method, url, body)?;
+        if !resp.is_json() {
+            let content_type = match resp.headers.get(CONTENT_TYPE) {
+                Some(value) => value.to_str().unwrap_or_default().to_string(),
+                None => "no content type".to_string(),
+            };
+            return Err(ApiError::InvalidResponse { content_type }.into());
+        }
+
+        let value: Value = serde_json::from_slice(&resp.body)?;
+        if let Some(error) = value.get("error") {
+            let message = error_message(error);
+            return Err(ApiError::Rejected { message }.into());
+        }
+
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+/// A response that has been read in full
+pub struct RawResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl RawResponse {
+    fn read(resp: Response) -> reqwest::Result<RawResponse> {
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        let body = resp.bytes()?.to_vec();
+        Ok(RawResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(|mime| {
+                let mime = mime.trim();
+                mime == "application/json" || mime.ends_with("+json")
+            })
+            .unwrap_or(false)
+    }
+}
+
+fn is_connect_error(err: &Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .is_some_and(reqwest::Error::is_connect)
+}
+
+/// Generate a random (version 4) UUID to identify a request
+fn request_id() -> String {
+    let mut bytes = [0u8; 16];
+    if SystemRandom::new().fill(&mut bytes).is_err() {
+        warn!("Could not generate a random request ID");
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Exponential backoff starting at half a second, with half of the delay
+/// randomized so that clients don't retry in lockstep
+fn backoff(attempt: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(RETRY_MAX_DELAY);
+
+    let mut random = [0; 4];
+    let jitter = match SystemRandom::new().fill(&mut random) {
+        Ok(()) => u32::from_be_bytes(random) as f64 / u32::MAX as f64,
+        Err(_) => 0.5,
+    };
+    delay / 2 + delay.mul_f64(jitter / 2.0)
+}
+
+/// Parse the `Retry-After` header, given either in seconds or as a date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    let delay = match value.parse::<u64>() {
+        Ok(seconds) => Duration::from_secs(seconds),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()?
+            .duration_since(SystemTime::now())
+            .unwrap_or_default(),
+    };
+    Some(delay.min(RETRY_AFTER_MAX))
+}
+
+fn error_message(error: &Value) -> String {
+    match error {
+        Value::String(message) => message.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn client(
+    config: &Config,
+    cookie_store: &Arc<CookieStoreMutex>,
 ) -> Result<reqwest::blocking::Client> {
-    let client = reqwest::blocking::ClientBuilder::new()
+    let mut builder = reqwest::blocking::ClientBuilder::new()
         .cookie_store(true)
         .cookie_provider(Arc::clone(cookie_store))
-        .build()?;
-    Ok(client)
+        .connect_timeout(timeout(config.connect_timeout))
+        .timeout(timeout(config.timeout))
+        .default_headers(headers(config)?);
+
+    if let Some(url) = &config.proxy {
+        let proxy = Proxy::all(url.as_str())?
+            .no_proxy(config.no_proxy.as_deref().and_then(NoProxy::from_string));
+        builder = builder.proxy(proxy);
+    }
+
+    if let Some(tls) = tls::client_config(config)? {
+        builder = builder.use_preconfigured_tls(tls);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Convert a timeout in seconds, where 0 means no timeout
+fn timeout(seconds: u64) -> Option<Duration> {
+    match seconds {
+        0 => None,
+        seconds => Some(Duration::from_secs(seconds)),
+    }
+}
+
+/// Build the extra headers sent with every request
+fn headers(config: &Config) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &config.headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| Error::msg(format!("Invalid header name {name}")))?;
+        let mut value = HeaderValue::from_str(value)
+            .map_err(|_| Error::msg(format!("Invalid value for header {name}")))?;
+        value.set_sensitive(redact::is_secret(name.as_str()));
+        headers.insert(name, value);
+    }
+    Ok(headers)
 }
 
 pub fn maybe_add_auth(request: RequestBuilder, token: Option<String>) -> RequestBuilder {
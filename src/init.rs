@@ -0,0 +1,189 @@
+use crate::prompts;
+use crate::Args;
+use jwctl::command;
+use jwctl::config::{self, Config};
+use jwctl::errors::ApiError;
+use jwctl::http::ApiClient;
+
+use anyhow::{Error, Result};
+use inquire::{validator::Validation, Confirm, CustomUserError, Password, Select, Text};
+use serde_json::{to_string_pretty, Value};
+
+const TOKEN_AUTH: &str = "Bearer token";
+const SSO_AUTH: &str = "SSO login";
+const SKIP_AUTH: &str = "Skip for now";
+
+const RETRY_URL: &str = "Enter another URL";
+const KEEP_URL: &str = "Use it anyway";
+const ABORT: &str = "Abort";
+
+/// Walk through configuring jwctl for a JumpWire gateway, then write the
+/// configuration file and token file. When a context is selected, its URL
+/// is set instead of the top-level one.
+pub fn run(args: &Args) -> Result<()> {
+    let context = config::list_contexts(args.overrides())?
+        .into_iter()
+        .find(|context| context.current)
+        .map(|context| context.name);
+    match &context {
+        Some(name) => info!("Let's connect context {name} to your JumpWire gateway."),
+        None => info!("Let's connect jwctl to your JumpWire gateway."),
+    }
+
+    let mut config = prompt_for_gateway(args)?;
+    config.context = context.clone();
+
+    let options = vec![TOKEN_AUTH, SSO_AUTH, SKIP_AUTH];
+    let token = match Select::new("How do you want to authenticate?", options).prompt()? {
+        TOKEN_AUTH => Some(prompt_for_token(&config)?),
+        SSO_AUTH => {
+            sso_login(&config)?;
+            None
+        }
+        _ => None,
+    };
+
+    let config_file = config::config_file(args.config.as_deref())?;
+    let key = match &context {
+        Some(name) => format!("contexts.{name}.url"),
+        None => "url".to_string(),
+    };
+    config::set_value(&config_file, &key, config.url.as_str())?;
+    if let Some(token) = token {
+        // Reload the configuration so the token is kept in the configured credential store
+        let saved = config::load_config(args.overrides())?;
+        config::save_token(&saved, token)?;
+    }
+
+    info!("Configuration saved to {:?}", config_file);
+    Ok(())
+}
+
+fn url_validator(input: &str) -> Result<Validation, CustomUserError> {
+    match url::Url::parse(input) {
+        Ok(_) => Ok(Validation::Valid),
+        Err(err) => Ok(Validation::Invalid(format!("Invalid URL: {err}").into())),
+    }
+}
+
+/// Ask for the gateway URL until one is reachable, or the user accepts it
+/// anyway or gives up. The gateway is checked with the rest of the current
+/// configuration, such as its CA certificate, proxy and headers.
+fn prompt_for_gateway(args: &Args) -> Result<Config> {
+    loop {
+        let url = Text::new("What is the URL of your JumpWire gateway?")
+            .with_default("http://localhost:4004")
+            .with_validator(url_validator)
+            .prompt()?;
+        let mut overrides = args.overrides();
+        overrides.url = Some(url::Url::parse(&url)?);
+        let config = config::load_config(overrides)?;
+        let api = ApiClient::new(&config)?;
+
+        if let Err(err) = command::ping(&api) {
+            error!("Could not reach {}: {:#}", url, err);
+            match keep_url()? {
+                true => return Ok(config),
+                false => continue,
+            }
+        }
+
+        match command::status(&api) {
+            Ok(status) => {
+                info!("Connected! Remote status:\n{}", to_string_pretty(&status)?);
+                return Ok(config);
+            }
+            Err(err) if is_auth_error(&err) => {
+                info!("Connected! Authentication is required to view the gateway status.");
+                return Ok(config);
+            }
+            Err(err) => {
+                warn!("{} did not return a valid status: {:#}", url, err);
+                if keep_url()? {
+                    return Ok(config);
+                }
+            }
+        }
+    }
+}
+
+/// Whether the gateway refused a request for lack of valid credentials
+fn is_auth_error(err: &Error) -> bool {
+    matches!(
+        err.downcast_ref::<ApiError>(),
+        Some(ApiError::Unauthorized { .. } | ApiError::Forbidden { .. })
+    )
+}
+
+/// Ask whether to keep a URL that didn't work, enter another one or abort
+fn keep_url() -> Result<bool> {
+    let options = vec![RETRY_URL, KEEP_URL, ABORT];
+    match Select::new("What do you want to do?", options).prompt()? {
+        RETRY_URL => Ok(false),
+        KEEP_URL => Ok(true),
+        _ => Err(Error::msg("No gateway URL configured")),
+    }
+}
+
+/// Ask for a bearer token until one is accepted by the gateway.
+fn prompt_for_token(config: &Config) -> Result<String> {
+    loop {
+        let token = Password::new("What is your JumpWire token?")
+            .without_confirmation()
+            .prompt()?;
+
+        let mut candidate = config.clone();
+        candidate.token = Some(token.clone());
+
+        match command::token_whoami(&ApiClient::new(&candidate)?) {
+            Ok(resp) => {
+                info!("Token is valid:\n{}", to_string_pretty(&resp)?);
+                return Ok(token);
+            }
+            Err(err) if err.is::<ApiError>() => error!("Token was rejected: {:#}", err),
+            Err(err) => error!("Could not validate token: {:#}", err),
+        }
+
+        let retry = Confirm::new("Try another token?")
+            .with_default(true)
+            .prompt()?;
+        if !retry {
+            return Err(Error::msg("No valid token provided"));
+        }
+    }
+}
+
+/// Choose one of the configured SSO providers and log in with it.
+fn sso_login(config: &Config) -> Result<()> {
+    let api = ApiClient::new(config)?;
+    let providers = provider_names(&command::auth_list(&api)?);
+    if providers.is_empty() {
+        return Err(Error::msg("The gateway has no SSO providers configured"));
+    }
+
+    let provider = Select::new("Select an SSO provider", providers).prompt()?;
+    prompts::sso_login(&api, &provider)?;
+    info!("Authenticated!");
+    Ok(())
+}
+
+/// Extract provider names from the SSO listing, which is either a list of
+/// names or objects, or a map keyed by name.
+fn provider_names(resp: &Value) -> Vec<String> {
+    match resp {
+        Value::Array(items) => items
+            .iter()
+            .filter_map(|item| match item {
+                Value::String(name) => Some(name.clone()),
+                Value::Object(obj) => obj
+                    .get("name")
+                    .or_else(|| obj.get("id"))
+                    .and_then(Value::as_str)
+                    .map(String::from),
+                _ => None,
+            })
+            .collect(),
+        Value::Object(obj) => obj.keys().cloned().collect(),
+        _ => vec![],
+    }
+}
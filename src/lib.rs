@@ -0,0 +1,74 @@
+//! Client library for the JumpWire API, used by the `jwctl` CLI.
+//!
+//! Configuration is loaded the same way as the CLI does, from the
+//! configuration files, `JW_*` environment variables and the credential
+//! store. An [`http::ApiClient`] built from it is passed to the request
+//! functions in [`command`], [`manifests`] and [`schemas`], which return
+//! the records defined in [`models`]:
+//!
+//! ```no_run
+//! use jwctl::config::{load_config, Overrides};
+//! use jwctl::http::ApiClient;
+//! use jwctl::manifests;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let config = load_config(Overrides::default())?;
+//! let api = ApiClient::new(&config)?;
+//! let manifests = manifests::list(&api)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The library doesn't print anything or prompt for input. Diagnostics are
+//! only emitted with the `log` crate when the `cli` feature is enabled, the
+//! passphrase of the encrypted credential store is asked for with the prompt
+//! set with [`credentials::set_passphrase_prompt`], and the request previews
+//! of `dry_run` and `print_curl` are shown with the printer set with
+//! [`http::set_preview_printer`].
+
+#[cfg(feature = "cli")]
+#[macro_use]
+extern crate log;
+
+/// Without the `cli` feature nothing is logged, and the log statements
+/// compile to nothing while still checking their arguments.
+#[cfg(not(feature = "cli"))]
+#[macro_use]
+mod no_log {
+    macro_rules! debug {
+        ($($arg:tt)+) => {
+            if false {
+                let _ = format_args!($($arg)+);
+            }
+        };
+    }
+
+    macro_rules! info {
+        ($($arg:tt)+) => {
+            debug!($($arg)+)
+        };
+    }
+
+    macro_rules! warn {
+        ($($arg:tt)+) => {
+            debug!($($arg)+)
+        };
+    }
+}
+
+pub mod api;
+mod cassette;
+pub mod command;
+pub mod compat;
+pub mod config;
+pub mod credentials;
+pub mod errors;
+pub mod http;
+pub mod manifests;
+pub mod models;
+mod permissions;
+mod preview;
+mod redact;
+pub mod schemas;
+mod tls;
+mod yaml_edit;
@@ -1,19 +1,28 @@
-mod command;
-mod config;
-mod http;
-mod manifests;
-mod schemas;
+mod doctor;
+mod init;
+mod output;
+mod prompts;
+mod query;
+mod template;
 mod terminal;
 
 #[macro_use]
 extern crate log;
-extern crate config as config_rs;
+
+use jwctl::config::Config;
+use jwctl::{api, command, compat, config, credentials, errors, http, manifests, schemas};
+use output::Format;
+use query::Query;
+use template::Template;
 
 use anyhow::{Error, Result};
 use clap::{Parser, Subcommand, ValueEnum};
 use log::{LevelFilter, SetLoggerError};
-use serde_json::to_string_pretty;
+use serde::Serialize;
+use serde_json::{json, Value};
 use simplelog::TermLogger;
+use std::path::PathBuf;
+use std::process::ExitCode;
 use strum_macros::Display;
 
 #[derive(Clone, Parser, Debug)]
@@ -26,9 +35,30 @@ pub struct Args {
     #[arg(short, long)]
     url: Option<url::Url>,
 
-    /// Enable verbose logging
-    #[arg(short, long)]
-    verbose: bool,
+    /// Enable verbose logging. Repeat to trace HTTP requests (-vv) and
+    /// their headers and bodies (-vvv)
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Format of command results. Each command has its own default
+    #[arg(short, long, global = true, value_enum)]
+    output: Option<Format>,
+
+    /// Filter command results with a jq expression before printing them
+    #[arg(long, global = true, value_parser = Query::parse)]
+    query: Option<Query>,
+
+    /// Render command results with a Go-style template, eg '{{range .}}{{.name}}\n{{end}}'
+    #[arg(long, global = true, value_parser = parse_template, conflicts_with_all = ["output", "template_file"])]
+    template: Option<Template>,
+
+    /// Render command results with a template read from a file
+    #[arg(long, global = true, value_name = "FILE", value_parser = read_template, conflicts_with = "output")]
+    template_file: Option<Template>,
+
+    /// Don't log anything, only print results and errors
+    #[arg(short, long, global = true)]
+    quiet: bool,
 
     /// Enable timestamps in log lines
     #[arg(long)]
@@ -37,6 +67,58 @@ pub struct Args {
     /// Token to use for authenticating to the JumpWire API
     #[arg(short, long)]
     token: Option<String>,
+
+    /// Name of the context from the configuration file to use
+    #[arg(long)]
+    context: Option<String>,
+
+    /// Path of the configuration file to use instead of the default one
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Send reads as usual, but print the first request that would change anything instead of sending it
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Show request bodies in `--dry-run` and `--print-curl` previews without redacting credentials
+    #[arg(long)]
+    raw_body: bool,
+
+    /// Print each request as an equivalent curl command
+    #[arg(long)]
+    print_curl: bool,
+
+    /// Record every request and response to a cassette file, with credentials redacted
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Replay responses from a cassette file instead of sending requests
+    #[arg(long, value_name = "FILE")]
+    replay: Option<PathBuf>,
+
+    /// How many times to retry requests when the proxy server is unavailable
+    #[arg(long)]
+    retries: Option<u32>,
+
+    /// PEM file with additional CA certificates to trust
+    #[arg(long)]
+    ca_cert: Option<PathBuf>,
+
+    /// PEM file with a client certificate for mutual TLS
+    #[arg(long)]
+    client_cert: Option<PathBuf>,
+
+    /// PEM file with the private key for the client certificate
+    #[arg(long)]
+    client_key: Option<PathBuf>,
+
+    /// Disable verification of the proxy server's TLS certificate
+    #[arg(long)]
+    insecure_skip_verify: bool,
+
+    /// Comma separated SHA-256 hashes of public keys to pin the proxy server's certificate to
+    #[arg(long, value_delimiter = ',')]
+    spki_pins: Vec<String>,
 }
 
 #[derive(Clone, Debug, Subcommand)]
@@ -88,12 +170,100 @@ enum Commands {
         #[command(subcommand)]
         command: SchemaCommands,
     },
+
+    /// Check the configuration, file permissions and connectivity to the proxy server
+    Doctor,
+
+    /// Print the version of jwctl
+    Version {
+        /// Also print the version of the proxy server, and the commands it doesn't support
+        #[arg(long)]
+        server: bool,
+    },
+
+    /// Make an authenticated request to any API endpoint
+    ///
+    /// Example: `jwctl api GET /api/v1/manifests --jq '.[].name'`
+    #[command(arg_required_else_help = true)]
+    Api {
+        /// The HTTP method, such as GET or POST
+        method: String,
+
+        /// The path of the endpoint, such as /api/v1/status
+        path: String,
+
+        /// A key=value field to send in the JSON body, or in the query string of GET requests
+        #[arg(short = 'f', long = "field")]
+        fields: Vec<String>,
+
+        /// Read the JSON body from a file, or from stdin with `-`
+        #[arg(long)]
+        input: Option<PathBuf>,
+
+        /// Fetch every page by following `rel="next"` links
+        #[arg(long)]
+        paginate: bool,
+
+        /// Filter each page with a jq expression, the same as `--query`
+        #[arg(long, value_parser = Query::parse, conflicts_with = "query")]
+        jq: Option<Query>,
+    },
 }
 
 #[derive(Clone, Debug, Subcommand)]
 enum ConfigCommands {
     /// Display the current configuration
-    Get,
+    Get {
+        /// Show where each value was loaded from
+        #[arg(long)]
+        show_origin: bool,
+
+        /// Display secret values, such as the token, instead of redacting them
+        #[arg(long)]
+        show_secrets: bool,
+    },
+
+    /// Interactively configure jwctl for a JumpWire gateway
+    Init,
+
+    /// Set the context to use by default
+    #[command(arg_required_else_help = true)]
+    UseContext {
+        /// The name of the context
+        name: String,
+    },
+
+    /// List the contexts defined in the configuration file
+    GetContexts,
+
+    /// Set a value in the configuration file
+    ///
+    /// Example: `jwctl config set contexts.staging.url https://staging.example.com`
+    #[command(arg_required_else_help = true)]
+    Set {
+        /// The key to set. Nested keys are separated by dots.
+        key: String,
+
+        /// The value to store
+        value: String,
+    },
+
+    /// Remove a value from the configuration file
+    #[command(arg_required_else_help = true)]
+    Unset {
+        /// The key to remove. Nested keys are separated by dots.
+        key: String,
+    },
+
+    /// Display the configuration file, or a single value from it
+    View {
+        /// The key to display. Nested keys are separated by dots.
+        key: Option<String>,
+
+        /// Display the user and password of URLs instead of redacting them
+        #[arg(long)]
+        show_secrets: bool,
+    },
 }
 
 #[derive(Clone, Debug, Subcommand)]
@@ -101,6 +271,9 @@ enum TokenCommands {
     /// Store the authenticate token for future calls
     Set,
 
+    /// Remove the stored authentication token
+    Clear,
+
     /// Check permissions on the configured token
     Whoami,
 
@@ -165,13 +338,10 @@ enum ClientCommands {
         /// The ID of the client
         id: String,
 
-        /// Print just the token without other log messages
+        /// Print the connection settings as YAML, a URL or just the token.
+        /// Otherwise they are printed in the `--output` format, YAML by default
         #[arg(short, long)]
-        quiet: bool,
-
-        /// How to format the output
-        #[arg(short, long, default_value_t = OutputFormat::Yaml)]
-        format: OutputFormat,
+        format: Option<TokenFormat>,
     },
 }
 
@@ -184,7 +354,7 @@ enum DatabaseType {
 
 #[derive(Clone, Debug, Display, PartialEq, Eq, ValueEnum)]
 #[strum(serialize_all = "snake_case")]
-enum OutputFormat {
+enum TokenFormat {
     Yaml,
     Url,
     Raw,
@@ -246,36 +416,143 @@ pub enum SchemaCommands {
     Create,
 }
 
-impl config_rs::Source for Args {
-    fn clone_into_box(&self) -> Box<dyn config_rs::Source + Send + Sync> {
-        Box::new((*self).clone())
+impl Args {
+    /// The configuration values given with flags
+    fn overrides(&self) -> config::Overrides {
+        let mut overrides = config::Overrides::default();
+        overrides.url = self.url.clone();
+        overrides.token = self.token.clone();
+        overrides.context = self.context.clone();
+        overrides.config = self.config.clone();
+        overrides.retries = self.retries;
+        overrides.ca_cert = self.ca_cert.clone();
+        overrides.client_cert = self.client_cert.clone();
+        overrides.client_key = self.client_key.clone();
+        overrides.insecure_skip_verify = self.insecure_skip_verify;
+        overrides.spki_pins = self.spki_pins.clone();
+        overrides.dry_run = self.dry_run;
+        overrides.raw_body = self.raw_body;
+        overrides.print_curl = self.print_curl;
+        overrides.verbosity = self.verbose;
+        overrides.record = self.record.clone();
+        overrides.replay = self.replay.clone();
+        overrides
     }
 
-    fn collect(&self) -> Result<config_rs::Map<String, config_rs::Value>, config_rs::ConfigError> {
-        let mut m = config_rs::Map::new();
+    /// Whether results should be printed as data rather than the command's own text
+    fn structured_output(&self) -> bool {
+        self.output.is_some() || self.query.is_some() || self.template().is_some()
+    }
 
-        match &self.url {
-            Some(url) => {
-                let value = config_rs::ValueKind::String(url.to_string());
-                m.insert("url".to_string(), value.into());
-            }
-            None => (),
-        };
+    fn template(&self) -> Option<&Template> {
+        self.template.as_ref().or(self.template_file.as_ref())
+    }
+}
 
-        match &self.token {
-            Some(token) => {
-                let value = config_rs::ValueKind::String(token.to_string());
-                m.insert("token".to_string(), value.into());
+/// Parse a `--template`, with `\n`, `\t` and `\\` escapes replaced so that
+/// templates can be written on the command line
+fn parse_template(source: &str) -> Result<Template> {
+    let mut unescaped = String::new();
+    let mut chars = source.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => unescaped.push('\n'),
+            ('\\', Some('t')) => unescaped.push('\t'),
+            ('\\', Some('\\')) => unescaped.push('\\'),
+            _ => {
+                unescaped.push(c);
+                continue;
             }
-            None => (),
-        };
+        }
+        chars.next();
+    }
+    Template::parse(&unescaped)
+}
+
+fn read_template(path: &str) -> Result<Template> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|err| Error::msg(format!("Could not read template {path}: {err}")))?;
+    Template::parse(&source)
+}
 
-        Ok(m)
+/// Connection settings for a proxy client, printed by `client token`
+#[derive(Serialize)]
+struct Connection<'a> {
+    #[serde(rename = "type")]
+    protocol: &'a str,
+    host: &'a str,
+    port: u32,
+    username: &'a str,
+    password: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    database: Option<&'a str>,
+}
+
+/// Print a command's result in the `--output` format, or the command's
+/// default. With `--query`, each value the query selects is printed instead.
+fn print_result(args: &Args, result: &impl Serialize, default: Format) -> Result<()> {
+    let Some(query) = &args.query else {
+        return print_value(args, result, default);
+    };
+    for value in query.run(&serde_json::to_value(result)?)? {
+        print_value(args, &value, default)?;
+    }
+    Ok(())
+}
+
+/// Print a single value. Strings are printed without quotes unless an
+/// output format was chosen, and with a template nothing is added to what
+/// it renders.
+fn print_value(args: &Args, value: &impl Serialize, default: Format) -> Result<()> {
+    if let Some(template) = args.template() {
+        print!("{}", template.render(&serde_json::to_value(value)?)?);
+        return Ok(());
+    }
+    match (args.output, serde_json::to_value(value)?) {
+        (None, Value::String(s)) => {
+            println!("{s}");
+            Ok(())
+        }
+        (format, _) => output::print(value, format.unwrap_or(default)),
+    }
+}
+
+/// Print out the configuration, optionally annotated with where each value came from
+fn config_get(args: &Args, config: Config, show_origin: bool, show_secrets: bool) -> Result<()> {
+    let values = config.values(show_secrets)?;
+    let origin = |key: &str, value: &str| match config.origin(key) {
+        Some(origin) => origin.describe(key),
+        None if value == "~" => "unset".to_string(),
+        None => "default".to_string(),
+    };
+
+    if args.structured_output() {
+        let result: serde_json::Map<String, Value> = values
+            .into_iter()
+            .map(|(key, value)| {
+                let result = match show_origin {
+                    true => json!({"value": value, "origin": origin(&key, &value)}),
+                    false => Value::String(value),
+                };
+                (key, result)
+            })
+            .collect();
+        return print_result(args, &result, Format::Json);
+    }
+
+    for (key, value) in values {
+        match show_origin {
+            true => println!("{key}: {value}\t# {}", origin(&key, &value)),
+            false => println!("{key}: {value}"),
+        }
     }
+    Ok(())
 }
 
 fn setup_logging(args: &Args) -> Result<(), SetLoggerError> {
-    let log_level = if args.verbose {
+    let log_level = if args.quiet {
+        LevelFilter::Off
+    } else if args.verbose > 0 {
         LevelFilter::Debug
     } else {
         LevelFilter::Info
@@ -296,81 +573,168 @@ fn setup_logging(args: &Args) -> Result<(), SetLoggerError> {
     TermLogger::init(
         log_level,
         config,
-        simplelog::TerminalMode::Mixed,
+        simplelog::TerminalMode::Stderr,
         simplelog::ColorChoice::Auto,
     )
 }
 
-fn main() -> Result<()> {
+fn load_config(args: &Args) -> Result<config::Config> {
+    match config::load_config(args.overrides()) {
+        Ok(config) => Ok(config),
+        Err(err) => {
+            error!(
+                "Invalid configuration!\njwctl configuration can be read from:\n\t- {:?}\n\t- .jwctl.yaml in the current directory or its parents\n\t- Environmenal variables prefixed with JW_, eg JW_URL\n\t- CLI flags",
+                config::config_file(args.config.as_deref())?
+            );
+            Err(err.context(errors::InvalidConfig))
+        }
+    }
+}
+
+fn run_config_command(args: &Args, command: &ConfigCommands) -> Result<()> {
+    let config_file = config::config_file(args.config.as_deref())?;
+
+    match command {
+        ConfigCommands::Get {
+            show_origin,
+            show_secrets,
+        } => config_get(args, load_config(args)?, *show_origin, *show_secrets)?,
+        ConfigCommands::Init => init::run(args)?,
+        ConfigCommands::UseContext { name } => {
+            config::use_context(&config_file, name)?;
+            info!("Switched to context {}", name);
+        }
+        ConfigCommands::GetContexts => {
+            let contexts = config::list_contexts(args.overrides())?;
+            if args.structured_output() {
+                return print_result(args, &contexts, Format::Table);
+            }
+            println!("{:7} {:20} URL", "CURRENT", "NAME");
+            contexts.iter().for_each(|c| {
+                let current = if c.current { "*" } else { "" };
+                let url = c.url.as_deref().unwrap_or("");
+                println!("{:7} {:20} {}", current, c.name, url)
+            });
+        }
+        ConfigCommands::Set { key, value } => {
+            config::set_value(&config_file, key, value)?;
+            info!("Set {} in {:?}", key, config_file);
+        }
+        ConfigCommands::Unset { key } => {
+            config::unset_value(&config_file, key)?;
+            info!("Removed {} from {:?}", key, config_file);
+        }
+        ConfigCommands::View { key, show_secrets } => {
+            let view = config::view(&config_file, key.as_deref(), *show_secrets)?;
+            match args.structured_output() {
+                true => print_result(args, &serde_yaml::from_str::<Value>(&view)?, Format::Yaml)?,
+                false => print!("{}", view),
+            }
+        }
+    };
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) if err.is::<errors::DryRun>() => {
+            info!("{}", err);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            ExitCode::from(errors::exit_code(&err))
+        }
+    }
+}
+
+fn run() -> Result<()> {
     let args = Args::parse();
 
     setup_logging(&args)?;
     debug!("Debug logging enabled");
+    credentials::set_passphrase_prompt(prompts::passphrase);
+    http::set_preview_printer(|preview| eprintln!("{preview}"));
+
+    // Commands managing or checking the configuration don't require it to be valid
+    let config = match &args.command {
+        Commands::Config { command } => return run_config_command(&args, command),
+        Commands::Doctor => return doctor::run(&args),
+        Commands::Version { server: false } => return print_version(&args, None),
+        _ => load_config(&args)?,
+    };
 
-    let config_file = config::config_file()?;
-    let config = config::load_config(args.clone()).map_err(|err| -> Error {
-        error!(
-            "Invalid configuration!\njwctl configuration can be read from:\n\t- {:?}\n\t- Environmenal variables prefixed with JW_, eg JW_URL\n\t- CLI flags",
-            config_file
-        );
-        err
-    })?;
+    let api = http::ApiClient::new(&config)?;
+    run_command(&args, config, &api)
+}
 
+/// Run a command that talks to the gateway
+fn run_command(args: &Args, config: Config, api: &http::ApiClient) -> Result<()> {
     match &args.command {
-        Commands::Config { command } => match command {
-            ConfigCommands::Get => command::config_get(config)?,
-        },
+        Commands::Config { .. } | Commands::Doctor | Commands::Version { server: false } => {
+            unreachable!("handled above")
+        }
+        Commands::Version { server: true } => {
+            let version = compat::server_version(api)?;
+            print_version(
+                args,
+                Some(version.map_or("unknown".to_string(), |v| v.to_string())),
+            )?;
+        }
         Commands::Status => {
-            let resp = command::status(config)?;
-            info!("Remote status:\n{}", to_string_pretty(&resp)?);
+            let resp = command::status(api)?;
+            print_result(args, &resp, Format::Json)?;
         }
         Commands::Ping => {
-            let resp = command::ping(config)?;
-            info!("Ping response: {:?}", resp);
+            let resp = command::ping(api)?;
+            print_result(args, &resp, Format::Json)?;
         }
         Commands::Token { command } => match command {
             TokenCommands::Set => {
                 command::authenticate(config)?;
                 info!("Authentication token stored!");
             }
+            TokenCommands::Clear => {
+                config::erase_token(&config)?;
+                info!("Authentication token removed!");
+            }
             TokenCommands::Whoami => {
-                let resp = command::token_whoami(config)?;
-                info!("whoami:\n{}", to_string_pretty(&resp)?);
+                let resp = command::token_whoami(api)?;
+                print_result(args, &resp, Format::Json)?;
             }
             TokenCommands::Generate { permissions } => {
-                let resp = command::generate_token(config, permissions)?;
-                info!("Token generated:\n{}", to_string_pretty(&resp)?);
+                let resp = command::generate_token(api, permissions)?;
+                info!("Token generated");
+                print_result(args, &resp, Format::Json)?;
             }
         },
         Commands::Auth { command } => match command {
             AuthCommands::List => {
-                let resp = command::auth_list(config)?;
-                info!(
-                    "Configured SSO identity providers:\n{}",
-                    to_string_pretty(&resp)?
-                );
+                let resp = command::auth_list(api)?;
+                print_result(args, &resp, Format::Json)?;
             }
             AuthCommands::Login { provider } => {
-                let resp = command::auth_login(config, provider)?;
-                match resp.get("error") {
-                    Some(err) => error!("{}", to_string_pretty(&err)?),
-                    _ => info!("Authenticated!"),
-                };
+                prompts::sso_login(api, provider)?;
+                info!("Authenticated!");
             }
             AuthCommands::Whoami => {
-                let resp = command::sso_whoami(config)?;
-                info!("whoami:\n{}", to_string_pretty(&resp)?);
+                let resp = command::sso_whoami(api)?;
+                print_result(args, &resp, Format::Json)?;
             }
         },
         Commands::Db { command } => match command {
             DbCommands::List { db_type } => {
-                let dbs = command::list_dbs(config, db_type.to_string())?;
-                println!("{:36} Name", "ID");
-                dbs.iter()
-                    .for_each(|(id, name)| println!("{:} {:}", id, name));
+                let dbs = command::list_dbs(api, &db_type.to_string())?;
+                let dbs: Vec<Value> = dbs
+                    .into_iter()
+                    .map(|(id, name)| json!({"id": id, "name": name}))
+                    .collect();
+                print_result(args, &dbs, Format::Table)?;
             }
             DbCommands::Login { token } => {
-                let dbs = command::check_db_token(&config, token)?;
+                let dbs = command::check_db_token(api, token)?;
                 let items: Vec<(&String, &String)> = dbs.iter().collect();
                 if items.is_empty() {
                     error!("No matching databases!");
@@ -386,64 +750,119 @@ fn main() -> Result<()> {
                 terminal::restore_terminal(&mut term)?;
 
                 debug!("Authenticating to database {:}", id);
-                command::approve_db_authentication(&config, token, id)?;
+                command::approve_db_authentication(api, token, id)?;
                 info!("Authentication request to {:} is approved!", name);
             }
         },
         Commands::Client { command } => match command {
             ClientCommands::Get { id } => {
-                let resp = command::client_get(config, id)?;
-                info!("Client information:\n{}", to_string_pretty(&resp)?);
+                let resp = command::client_get(api, id)?;
+                print_result(args, &resp, Format::Json)?;
             }
-            ClientCommands::Token { id, quiet, format } => {
-                let data = command::client_token(&config, id)?;
-                if !*quiet {
-                    info!("Token generated\n");
-                }
+            ClientCommands::Token { id, format } => {
+                let data = command::client_token(api, id)?;
+                info!("Token generated");
                 let host = &config
                     .url
                     .host_str()
                     .ok_or(Error::msg("Missing host in URL"))?;
-                let database = data.database.unwrap_or("".to_string());
+                let connection = Connection {
+                    protocol: &data.protocol,
+                    host,
+                    port: data.port,
+                    username: &data.manifest_id,
+                    password: &data.token,
+                    database: data.database.as_deref(),
+                };
 
                 match format {
-                    OutputFormat::Raw => println!("{}", data.token),
-                    OutputFormat::Yaml => println!(
-                        "type: {}\nhost: {}\nport: {}\nusername: {}\npassword: {}",
-                        data.protocol, host, data.port, data.manifest_id, data.token
-                    ),
-                    OutputFormat::Url => println!(
+                    Some(TokenFormat::Raw) => println!("{}", data.token),
+                    Some(TokenFormat::Yaml) => output::print(&connection, Format::Yaml)?,
+                    Some(TokenFormat::Url) => println!(
                         "{}://{}:{}@{}:{}/{}",
-                        data.protocol, data.manifest_id, data.token, host, data.port, database
+                        data.protocol,
+                        data.manifest_id,
+                        data.token,
+                        host,
+                        data.port,
+                        data.database.as_deref().unwrap_or_default()
                     ),
+                    None => print_result(args, &connection, Format::Yaml)?,
                 }
             }
         },
-        Commands::Manifest { command } => {
-            let restult = match command {
-                ManifestCommands::List => manifests::list(&config)?,
-                ManifestCommands::Get { id } => manifests::get_by_id(config, id.to_string())?,
-                ManifestCommands::Delete { id } => manifests::delete(config, id.to_string())?,
-                ManifestCommands::Create => manifests::create(config)?,
-            };
-
-            info!("{}", to_string_pretty(&restult)?);
-        }
-        Commands::Schema { command } => {
-            let result = match command {
-                SchemaCommands::List { id } => schemas::list(id.clone(), &config)?,
-                SchemaCommands::Get { manifest_id, id } => {
-                    schemas::get_by_id(manifest_id.to_string(), id.to_string(), config)?
-                }
-                SchemaCommands::Delete { manifest_id, id } => {
-                    schemas::delete(manifest_id.to_string(), id.to_string(), config)?
+        Commands::Manifest { command } => match command {
+            ManifestCommands::List => print_result(args, &manifests::list(api)?, Format::Json)?,
+            ManifestCommands::Get { id } => {
+                print_result(args, &manifests::get_by_id(api, id)?, Format::Json)?
+            }
+            ManifestCommands::Delete { id } => {
+                print_result(args, &manifests::delete(api, id)?, Format::Json)?
+            }
+            ManifestCommands::Create => {
+                let manifest = prompts::prompt_user_for_manifest()?;
+                print_result(args, &manifests::create(api, &manifest)?, Format::Json)?
+            }
+        },
+        Commands::Schema { command } => match command {
+            SchemaCommands::List { id } => {
+                let id = match id {
+                    Some(id) => id.clone(),
+                    None => prompts::select_manifest(api)?,
+                };
+                print_result(args, &schemas::list(api, &id)?, Format::Json)?
+            }
+            SchemaCommands::Get { manifest_id, id } => {
+                let schema = schemas::get_by_id(api, manifest_id, id)?;
+                print_result(args, &schema, Format::Json)?
+            }
+            SchemaCommands::Delete { manifest_id, id } => {
+                let resp = schemas::delete(api, manifest_id, id)?;
+                print_result(args, &resp, Format::Json)?
+            }
+            SchemaCommands::Create => {
+                let schema = prompts::prompt_for_schema(api)?;
+                print_result(args, &schemas::create(api, &schema)?, Format::Json)?
+            }
+        },
+        Commands::Api {
+            method,
+            path,
+            fields,
+            input,
+            paginate,
+            jq,
+        } => {
+            let pages = api::call(api, method, path, fields, input.as_deref(), *paginate)?;
+            for page in pages {
+                match jq {
+                    Some(jq) => {
+                        for value in jq.run(&page)? {
+                            print_value(args, &value, Format::Json)?;
+                        }
+                    }
+                    None => print_result(args, &page, Format::Json)?,
                 }
-                SchemaCommands::Create => schemas::create(config)?,
-            };
-
-            info!("{}", to_string_pretty(&result)?);
+            }
         }
     };
 
     Ok(())
 }
+
+/// Print the version of jwctl, and of the server when it was requested
+fn print_version(args: &Args, server: Option<String>) -> Result<()> {
+    let mut versions = serde_json::Map::new();
+    versions.insert("jwctl".to_string(), env!("CARGO_PKG_VERSION").into());
+    if let Some(server) = server {
+        versions.insert("server".to_string(), server.into());
+    }
+
+    if args.structured_output() {
+        return print_result(args, &versions, Format::Json);
+    }
+    for (name, version) in versions {
+        println!("{} {}", name, version.as_str().unwrap_or_default());
+    }
+    Ok(())
+}
@@ -0,0 +1,121 @@
+//! Records returned by the JumpWire API.
+//!
+//! Only the fields jwctl relies on are typed, and most of them are optional.
+//! Any other field is kept in `extra`, so that responses from newer
+//! gateways can still be parsed and displayed in full.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Fields of a record that don't have a typed field
+pub type Extra = BTreeMap<String, Value>;
+
+/// Status of the gateway, from `GET /api/v1/status`
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct Status {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// Permissions of the current token, from `GET /api/v1/token`. Permissions
+/// are grouped by method, eg `get: [status, manifests]`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct TokenPermissions {
+    #[serde(default)]
+    pub permissions: BTreeMap<String, Vec<String>>,
+
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// The user logged in with SSO, from `GET /sso/whoami`
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct SsoIdentity {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// A proxy client, from `GET /api/v1/client/<id>`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct ClientInfo {
+    pub id: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest_id: Option<String>,
+
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// A manifest stored on the gateway. The configuration and credentials are
+/// kept in `extra`, as their fields depend on the type of database.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct ManifestRecord {
+    pub id: String,
+
+    pub name: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root_type: Option<String>,
+
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// A proxy schema stored on the gateway
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct ProxySchemaRecord {
+    pub id: String,
+
+    pub name: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest_id: Option<String>,
+
+    /// Mapping of field names to their label
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// Credentials for a proxy client to connect to a database through the gateway
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct ClientTokenData {
+    pub id: String,
+    pub token: String,
+    pub manifest_id: String,
+    pub protocol: String,
+    pub port: u32,
+
+    #[serde(default)]
+    pub database: Option<String>,
+
+    #[serde(flatten)]
+    pub extra: Extra,
+}
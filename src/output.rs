@@ -0,0 +1,107 @@
+//! Rendering of command results on stdout, in the format chosen with
+//! `--output`. Log messages are written to stderr, so the results can be
+//! piped to other programs.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use strum_macros::Display;
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, ValueEnum)]
+#[strum(serialize_all = "snake_case")]
+pub enum Format {
+    Json,
+    Yaml,
+    Table,
+}
+
+/// Print a result to stdout. Fields are kept in the order the result
+/// serializes them.
+pub fn print(result: &impl Serialize, format: Format) -> Result<()> {
+    let rendered = match format {
+        Format::Json => serde_json::to_string_pretty(result)?,
+        Format::Yaml => serde_yaml::to_string(result)?,
+        // YAML mappings keep the order of the fields, unlike JSON objects
+        Format::Table => table(&serde_yaml::to_value(result)?),
+    };
+    if !rendered.is_empty() {
+        println!("{}", rendered.trim_end_matches('\n'));
+    }
+    Ok(())
+}
+
+/// Render a list of records as a table with a column for each of their
+/// fields. A single record is printed as a field and its value per line,
+/// and other lists with a value per line.
+fn table(value: &Value) -> String {
+    let records: Vec<&Mapping> = match value {
+        Value::Mapping(record) => {
+            let rows = record
+                .iter()
+                .map(|(key, value)| vec![cell(key), cell(value)]);
+            return columns(rows.collect());
+        }
+        Value::Sequence(items) if items.iter().all(Value::is_mapping) => {
+            items.iter().filter_map(Value::as_mapping).collect()
+        }
+        Value::Sequence(items) => return items.iter().map(cell).collect::<Vec<_>>().join("\n"),
+        value => return cell(value),
+    };
+    if records.is_empty() {
+        return String::new();
+    }
+
+    let mut fields: Vec<&Value> = vec![];
+    for key in records.iter().flat_map(|record| record.keys()) {
+        if !fields.contains(&key) {
+            fields.push(key);
+        }
+    }
+
+    let mut rows = vec![fields.iter().map(|f| cell(f).to_uppercase()).collect()];
+    for record in records {
+        rows.push(
+            fields
+                .iter()
+                .map(|field| record.get(*field).map(cell).unwrap_or_default())
+                .collect(),
+        );
+    }
+    columns(rows)
+}
+
+/// Align rows of cells into columns
+fn columns(rows: Vec<Vec<String>>) -> String {
+    let count = rows.first().map_or(0, Vec::len);
+    let widths: Vec<usize> = (0..count)
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    rows.iter()
+        .map(|row| {
+            let line: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect();
+            line.join("  ").trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A value in a table cell. Nested values are shown as compact JSON.
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        value => serde_json::to_string(value).unwrap_or_default(),
+    }
+}
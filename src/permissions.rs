@@ -0,0 +1,89 @@
+//! Creation of files and directories that are only accessible by the
+//! current user. Tokens, cookies and configuration can all contain secrets.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+
+#[cfg(unix)]
+const DIR_MODE: u32 = 0o700;
+#[cfg(unix)]
+const FILE_MODE: u32 = 0o600;
+
+/// Create a directory and any missing parents with mode 0700. An existing
+/// directory owned by the current user is restricted to mode 0700 too.
+pub fn create_dir(path: &Path) -> Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    builder.mode(DIR_MODE);
+    builder.create(path)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = fs::metadata(path)?;
+        let mode = metadata.mode() & 0o777;
+        // SAFETY: geteuid has no preconditions and always succeeds
+        let owned = metadata.uid() == unsafe { libc::geteuid() };
+        if mode & 0o077 != 0 && owned {
+            debug!("Changing the mode of {path:?} from {mode:o} to {DIR_MODE:o}");
+            fs::set_permissions(path, fs::Permissions::from_mode(DIR_MODE))?;
+        }
+    }
+    Ok(())
+}
+
+/// Create or truncate a file with mode 0600, tightening the permissions of
+/// an existing file.
+pub fn create_file(path: &Path) -> Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(FILE_MODE);
+    let file = options.open(path)?;
+
+    #[cfg(unix)]
+    file.set_permissions(fs::Permissions::from_mode(FILE_MODE))?;
+    Ok(file)
+}
+
+/// Write a file with mode 0600
+pub fn write_file(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    use std::io::Write;
+    create_file(path)?.write_all(contents.as_ref())?;
+    Ok(())
+}
+
+/// Return the permission bits of a file or directory that other users can
+/// access, or `None` if it is private or does not exist.
+pub fn loose_mode(path: &Path) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        let mode = fs::metadata(path).ok()?.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            return Some(mode);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+
+    None
+}
+
+/// Log a warning when a file or directory is accessible by other users
+pub fn warn_if_loose(path: &Path) {
+    if let Some(mode) = loose_mode(path) {
+        warn!(
+            "{:?} is accessible by other users (mode {:o}), run `chmod go-rwx {}` to fix it",
+            path,
+            mode,
+            path.display()
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Printable previews of API requests, used by `--dry-run` and `--print-curl`.
+
+use crate::config::Config;
+use crate::redact::{self, REDACTED};
+
+use reqwest::blocking::Request;
+use reqwest::header::{HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+
+/// Describe a request with its method, URL, headers and body. Sensitive
+/// headers, such as `Authorization`, and credentials in the body are
+/// redacted unless `raw_body` is set.
+pub fn describe(request: &Request, config: &Config, has_cookies: bool) -> String {
+    let mut lines = vec![format!("{} {}", request.method(), request.url())];
+
+    for (name, value) in headers(request, config) {
+        lines.push(format!("{}: {}", name, redact::header(&name, &value)));
+    }
+    if has_cookies {
+        lines.push(format!("Cookie: {REDACTED}"));
+    }
+
+    if let Some(body) = body(request, config.raw_body) {
+        lines.push(String::new());
+        lines.push(body);
+    }
+
+    lines.join("\n")
+}
+
+/// Build an equivalent curl command. The token is read from the `JW_TOKEN`
+/// environment variable instead of being included in the command, and
+/// other secret headers, the proxy's password and credentials in the body
+/// are redacted.
+pub fn curl(request: &Request, config: &Config) -> String {
+    let mut args = vec!["curl".to_string()];
+    args.push(format!("-X {}", request.method()));
+
+    for (name, value) in headers(request, config) {
+        let header = match name == AUTHORIZATION {
+            true => "\"Authorization: Bearer $JW_TOKEN\"".to_string(),
+            false => quote(&format!("{}: {}", name, redact::header(&name, &value))),
+        };
+        args.push(format!("-H {header}"));
+    }
+
+    if config.insecure_skip_verify {
+        args.push("--insecure".to_string());
+    }
+    if let Some(path) = &config.ca_cert {
+        args.push(format!("--cacert {}", quote(&path.display().to_string())));
+    }
+    if let Some(path) = &config.client_cert {
+        args.push(format!("--cert {}", quote(&path.display().to_string())));
+    }
+    if let Some(path) = &config.client_key {
+        args.push(format!("--key {}", quote(&path.display().to_string())));
+    }
+    if let Some(proxy) = &config.proxy {
+        args.push(format!(
+            "--proxy {}",
+            quote(&redact::userinfo(proxy.as_str()))
+        ));
+    }
+
+    if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+        let body = match config.raw_body {
+            true => String::from_utf8_lossy(body).to_string(),
+            false => redact::body(body),
+        };
+        args.push(format!("--data-raw {}", quote(&body)));
+    }
+
+    args.push(quote(request.url().as_str()));
+    args.join(" \\\n  ")
+}
+
+/// Headers set on the request, followed by the extra headers from the
+/// configuration that the client adds when sending it
+fn headers(request: &Request, config: &Config) -> Vec<(HeaderName, HeaderValue)> {
+    let mut headers: Vec<(HeaderName, HeaderValue)> = request
+        .headers()
+        .iter()
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+
+    for (name, value) in &config.headers {
+        if let (Ok(name), Ok(mut value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            value.set_sensitive(redact::is_secret(name.as_str()));
+            headers.push((name, value));
+        }
+    }
+
+    headers
+}
+
+/// The request body, pretty printed and with its credentials redacted when
+/// it is JSON. A `raw` body is shown as is.
+fn body(request: &Request, raw: bool) -> Option<String> {
+    let body = request.body()?.as_bytes()?;
+    let is_json = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+
+    match is_json {
+        true => serde_json::from_slice::<serde_json::Value>(body)
+            .and_then(|mut json| {
+                if !raw {
+                    redact::json(&mut json);
+                }
+                serde_json::to_string_pretty(&json)
+            })
+            .ok(),
+        false if raw => Some(String::from_utf8_lossy(body).to_string()),
+        false => Some(redact::body(body)),
+    }
+}
+
+/// Quote a value for a POSIX shell
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
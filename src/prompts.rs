@@ -0,0 +1,352 @@
+//! Interactive prompts for the commands that create resources on the
+//! gateway, and for the credentials jwctl asks for.
+
+use std::collections::HashMap;
+
+use jwctl::command;
+use jwctl::http::ApiClient;
+use jwctl::manifests::{
+    self, Configuration, Credentials, MysqlConfig, MysqlCredentials, NewManifest, PostgresqlConfig,
+    PostgresqlCredentials, RootType, VaultCredentials,
+};
+use jwctl::schemas::ProxySchema;
+
+use anyhow::{Error, Result};
+use inquire::{
+    validator::Validation, Confirm, CustomType, CustomUserError, InquireError, Password,
+    PasswordDisplayMode, Select, Text,
+};
+use serde_json::Value;
+
+/// Prompt for the passphrase of the encrypted credential store, confirming
+/// it when a new token is being stored
+pub fn passphrase(confirm: bool) -> Result<String> {
+    let prompt = Password::new("Passphrase for the encrypted token:")
+        .with_display_mode(PasswordDisplayMode::Masked);
+    let prompt = if confirm {
+        prompt
+    } else {
+        prompt.without_confirmation()
+    };
+    Ok(prompt.prompt()?)
+}
+
+/// Log in with an SSO provider. The login URL is opened in a browser, and
+/// the code displayed after authenticating is read from stdin.
+pub fn sso_login(api: &ApiClient, idp: &str) -> Result<Value> {
+    let url = command::sso_login_url(api, idp)?;
+
+    match open::that(url.as_str()) {
+        Ok(()) => (),
+        Err(err) => debug!("Failed to open URL automatically: {:}", err),
+    };
+
+    info!("The login URL will open automatically in your browser. If it does not, you can enter it directly:\n\n{:}\n\nAfter authenticating, enter the code displayed:", url.to_string());
+
+    let code = read_code()?;
+    command::sso_validate(api, &code)
+}
+
+fn read_code() -> Result<String> {
+    let mut guess = String::new();
+
+    std::io::stdin()
+        .read_line(&mut guess)
+        .map_err(|_| Error::msg("Failed to read line"))?;
+
+    Ok(guess.trim().to_string())
+}
+
+/// Prompt for a new proxy schema, starting with the manifest it is for
+pub fn prompt_for_schema(api: &ApiClient) -> Result<ProxySchema> {
+    let manifest_id = select_manifest(api)?;
+
+    let name = prompt_for_name().unwrap();
+    let fields = prompt_for_fields()?;
+
+    Ok(ProxySchema {
+        name,
+        manifest_id,
+        fields,
+    })
+}
+
+/// Prompt for a manifest to choose from the ones on the gateway
+pub fn select_manifest(api: &ApiClient) -> Result<String> {
+    let manifests = manifests::list(api)?;
+
+    let mut keys = HashMap::new();
+
+    let manifest_names: Vec<String> = manifests
+        .into_iter()
+        .map(|m| {
+            let key = format!("{} ({})", m.name, m.id);
+            keys.insert(key.clone(), m.id);
+            key
+        })
+        .collect();
+
+    let manifest_name = Select::new("Select a manifest", manifest_names)
+        .prompt()?
+        .to_string();
+
+    keys.get(&manifest_name)
+        .ok_or_else(|| Error::msg("Could not find manifest"))
+        .cloned()
+}
+
+fn prompt_for_fields() -> Result<HashMap<String, String>> {
+    let mut fields = HashMap::new();
+
+    loop {
+        let field_name = Text::new("Field name").prompt().unwrap();
+        let field_label = Select::new("Field label", vec!["pii", "secret"])
+            .prompt()
+            .unwrap()
+            .to_string();
+
+        fields.insert(field_name, field_label);
+
+        let add_another = Confirm::new("Add another field?")
+            .with_default(true)
+            .prompt()
+            .unwrap();
+
+        if !add_another {
+            break;
+        }
+    }
+
+    Ok(fields)
+}
+
+fn prompt_for_name() -> Result<String> {
+    let name = CustomType::<String>::new("What is the name of your proxy-schema?")
+        .with_parser(&|input| {
+            if input.is_empty() {
+                Err(())
+            } else {
+                let is_valid = input
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+                if !is_valid {
+                    return Err(());
+                }
+
+                Ok(input.to_string())
+            }
+        })
+        .with_help_message("Schema names must be alphanumeric, underscores, or dashes")
+        .with_error_message("Please use a valid name")
+        .prompt()
+        .unwrap();
+
+    Ok(name)
+}
+
+fn not_empty_validator(input: &str) -> Result<Validation, CustomUserError> {
+    if input.trim().is_empty() {
+        Ok(Validation::Invalid("The input cannot be empty.".into()))
+    } else {
+        Ok(Validation::Valid)
+    }
+}
+
+fn prompt_for_root_type() -> RootType {
+    let options: Vec<&str> = vec!["PostgreSQL", "MySQL"];
+
+    let ans: Result<&str, InquireError> =
+        Select::new("Select the type manifest you want to create.", options).prompt();
+
+    let choice = ans.unwrap();
+
+    match choice {
+        "PostgreSQL" => RootType::Postgresql,
+        "MySQL" => RootType::Mysql,
+        _ => panic!("Invalid option selected"),
+    }
+}
+
+fn prompt_for_vault() -> bool {
+    let ans = Confirm::new("Are you using Vault to generate your credentials?")
+        .with_default(false)
+        .with_help_message("If you're not sure, select 'no'")
+        .prompt();
+
+    ans.map_err(|_e| false).unwrap()
+}
+
+// PostgreSQL configuration prompt function
+fn prompt_for_postgresql_config() -> PostgresqlConfig {
+    let hostname = Text::new("What is your PostgreSQL hostname?")
+        .with_validator(not_empty_validator)
+        .prompt()
+        .unwrap();
+
+    let database = Text::new("What is your PostgreSQL database name?")
+        .with_validator(not_empty_validator)
+        .prompt()
+        .unwrap();
+
+    let schema_str = Text::new("What is your PostgreSQL schema?")
+        .with_default("public")
+        .prompt();
+
+    let port = CustomType::<u16>::new("What port number is PostgresSQL running on?")
+        .with_error_message("Please type a valid number")
+        .with_default(5432)
+        .prompt()
+        .unwrap();
+
+    let ssl = Confirm::new("Are you using SSL?")
+        .with_default(true)
+        .prompt()
+        .unwrap();
+
+    let schema = match schema_str {
+        Ok(schema) => {
+            if schema.is_empty() {
+                None
+            } else {
+                Some(schema)
+            }
+        }
+        Err(_) => None,
+    };
+
+    PostgresqlConfig {
+        type_field: RootType::Postgresql,
+        hostname,
+        database,
+        port: Some(port),
+        ssl: Some(ssl),
+        schema,
+    }
+}
+
+// MySQL configuration prompt function
+fn prompt_for_mysql_config() -> MysqlConfig {
+    let hostname = Text::new("What is your MySQL hostname?")
+        .with_validator(not_empty_validator)
+        .prompt()
+        .unwrap();
+
+    let database = Text::new("What is your MySQL database name?")
+        .with_validator(not_empty_validator)
+        .prompt()
+        .unwrap();
+
+    let port = CustomType::<u16>::new("What port number is MySQL running on?")
+        .with_error_message("Please type a valid number")
+        .with_default(3306)
+        .prompt()
+        .unwrap();
+
+    let ssl = Confirm::new("Are you using SSL?")
+        .with_default(true)
+        .prompt()
+        .unwrap();
+
+    MysqlConfig {
+        type_field: RootType::Mysql,
+        hostname,
+        database,
+        port: Some(port),
+        ssl: Some(ssl),
+    }
+}
+
+fn prompt_for_vault_credentials() -> VaultCredentials {
+    let database = Text::new("What Vault database are you using?")
+        .with_validator(not_empty_validator)
+        .prompt()
+        .unwrap();
+
+    let role = Text::new("What Vault role are you using?")
+        .with_validator(not_empty_validator)
+        .prompt()
+        .unwrap();
+
+    VaultCredentials { database, role }
+}
+
+// Prompt for PostgreSQL credentials
+fn prompt_for_postgresql_credentials() -> PostgresqlCredentials {
+    let username = Text::new("What is your PostgreSQL username?")
+        .with_validator(not_empty_validator)
+        .prompt()
+        .unwrap();
+
+    let password = Password::new("What is your PostgreSQL password?")
+        .with_display_mode(PasswordDisplayMode::Masked)
+        .with_validator(not_empty_validator)
+        .prompt()
+        .unwrap();
+
+    PostgresqlCredentials { username, password }
+}
+
+// Prompt for MySQL credentials
+fn prompt_for_mysql_credentials() -> MysqlCredentials {
+    let username = Text::new("What is your MySQL username?")
+        .with_validator(not_empty_validator)
+        .prompt()
+        .unwrap();
+
+    let password = Text::new("What is your MySQL password?")
+        .with_validator(not_empty_validator)
+        .prompt()
+        .unwrap();
+
+    MysqlCredentials { username, password }
+}
+
+/// Prompt for the settings of a new manifest
+pub fn prompt_user_for_manifest() -> Result<NewManifest> {
+    let name = CustomType::<String>::new("What is the name of your manifest?")
+        .with_parser(&|input| {
+            if input.is_empty() {
+                Err(())
+            } else {
+                let is_valid = input
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+                if !is_valid {
+                    return Err(());
+                }
+
+                Ok(input.to_string())
+            }
+        })
+        .with_help_message("Manifest names must be alphanumeric, underscores, or dashes")
+        .with_error_message("Please use a valid name")
+        .prompt()
+        .unwrap();
+
+    let root_type = prompt_for_root_type();
+
+    let configuration = match root_type {
+        RootType::Postgresql => Configuration::Postgresql(prompt_for_postgresql_config()),
+        RootType::Mysql => Configuration::Mysql(prompt_for_mysql_config()),
+    };
+
+    let is_vault = prompt_for_vault();
+    let credentials = match is_vault {
+        true => Credentials::Vault(prompt_for_vault_credentials()),
+        false => match root_type {
+            RootType::Postgresql => Credentials::Postgresql(prompt_for_postgresql_credentials()),
+            RootType::Mysql => Credentials::Mysql(prompt_for_mysql_credentials()),
+        },
+    };
+
+    let manifest = NewManifest {
+        name,
+        root_type,
+        configuration,
+        credentials,
+    };
+
+    Ok(manifest)
+}
@@ -0,0 +1,981 @@
+//! A small subset of the jq language, used to filter JSON responses.
+//!
+//! Supported are paths (`.a.b`, `."a b"`, `.[0]`, `.[]`), pipes, commas,
+//! comparisons, `and`/`or`, the `//` alternative operator, `?`, literals,
+//! array and object construction and a handful of builtin functions.
+
+use anyhow::{Error, Result};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+
+/// A parsed query that can be run against JSON values
+#[derive(Clone, Debug)]
+pub struct Query {
+    expr: Expr,
+}
+
+impl Query {
+    pub fn parse(source: &str) -> Result<Query> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.parse_pipe()?;
+        match parser.peek() {
+            None => Ok(Query { expr }),
+            Some(token) => Err(invalid(format!("unexpected {token}"))),
+        }
+    }
+
+    /// Run the query, returning every value it produces
+    pub fn run(&self, input: &Value) -> Result<Vec<Value>> {
+        eval(&self.expr, input)
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::msg(format!("Invalid query: {message}"))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Dot,
+    Ident(String),
+    Str(String),
+    Num(f64),
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Pipe,
+    Comma,
+    Colon,
+    Semicolon,
+    Question,
+    Op(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Dot => write!(f, "`.`"),
+            Token::Ident(name) => write!(f, "`{name}`"),
+            Token::Str(value) => write!(f, "string {value:?}"),
+            Token::Num(value) => write!(f, "number {value}"),
+            Token::LBracket => write!(f, "`[`"),
+            Token::RBracket => write!(f, "`]`"),
+            Token::LParen => write!(f, "`(`"),
+            Token::RParen => write!(f, "`)`"),
+            Token::LBrace => write!(f, "`{{`"),
+            Token::RBrace => write!(f, "`}}`"),
+            Token::Pipe => write!(f, "`|`"),
+            Token::Comma => write!(f, "`,`"),
+            Token::Colon => write!(f, "`:`"),
+            Token::Semicolon => write!(f, "`;`"),
+            Token::Question => write!(f, "`?`"),
+            Token::Op(op) => write!(f, "`{op}`"),
+        }
+    }
+}
+
+const OPERATORS: [&str; 7] = ["==", "!=", "<=", ">=", "//", "<", ">"];
+
+/// Builtin functions and their number of arguments
+const FUNCTIONS: &[(&str, usize)] = &[
+    ("empty", 0),
+    ("not", 0),
+    ("type", 0),
+    ("length", 0),
+    ("keys", 0),
+    ("has", 1),
+    ("map", 1),
+    ("select", 1),
+    ("first", 0),
+    ("last", 0),
+    ("sort", 0),
+    ("add", 0),
+    ("join", 1),
+    ("to_entries", 0),
+    ("tostring", 0),
+    ("tonumber", 0),
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let rest: String = chars[i..].iter().take(2).collect();
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if rest == ".." {
+            return Err(invalid(
+                "recursive descent with `..` is not supported".to_string(),
+            ));
+        }
+
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            i += op.len();
+            continue;
+        }
+
+        let token = match c {
+            '.' => Token::Dot,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            '|' => Token::Pipe,
+            ',' => Token::Comma,
+            ':' => Token::Colon,
+            ';' => Token::Semicolon,
+            '?' => Token::Question,
+            '"' => {
+                let (value, end) = string(&chars, i + 1)?;
+                i = end;
+                tokens.push(Token::Str(value));
+                continue;
+            }
+            c if c.is_ascii_digit()
+                || (c == '-' && chars.get(i + 1).is_some_and(char::is_ascii_digit)) =>
+            {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let value = text
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid number {text}")))?;
+                tokens.push(Token::Num(value));
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+                continue;
+            }
+            c => return Err(invalid(format!("unexpected character `{c}`"))),
+        };
+        tokens.push(token);
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+/// Read a string literal starting after its opening quote, returning it and
+/// the position after the closing quote
+fn string(chars: &[char], start: usize) -> Result<(String, usize)> {
+    let mut value = String::new();
+    let mut i = start;
+    while i < chars.len() {
+        match chars[i] {
+            '"' => return Ok((value, i + 1)),
+            '\\' => {
+                i += 1;
+                match chars.get(i) {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('r') => value.push('\r'),
+                    Some(c) => value.push(*c),
+                    None => break,
+                }
+            }
+            c => value.push(c),
+        }
+        i += 1;
+    }
+    Err(invalid("unterminated string".to_string()))
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Identity,
+    Literal(Value),
+    Index(Box<Expr>, Box<Expr>),
+    Iterate(Box<Expr>),
+    Optional(Box<Expr>),
+    Pipe(Box<Expr>, Box<Expr>),
+    Comma(Box<Expr>, Box<Expr>),
+    Alternative(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, &'static str, Box<Expr>),
+    Array(Option<Box<Expr>>),
+    Object(Vec<(Expr, Expr)>),
+    Call(String, Vec<Expr>),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, token: Token) -> Result<()> {
+        match self.next() {
+            Some(next) if next == token => Ok(()),
+            Some(next) => Err(invalid(format!("expected {token} but found {next}"))),
+            None => Err(invalid(format!("expected {token}"))),
+        }
+    }
+
+    fn parse_pipe(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_comma()?;
+        while self.eat(&Token::Pipe) {
+            expr = Expr::Pipe(Box::new(expr), Box::new(self.parse_comma()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_comma(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_alternative()?;
+        while self.eat(&Token::Comma) {
+            expr = Expr::Comma(Box::new(expr), Box::new(self.parse_alternative()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_alternative(&mut self) -> Result<Expr> {
+        let expr = self.parse_or()?;
+        match self.eat(&Token::Op("//")) {
+            true => Ok(Expr::Alternative(
+                Box::new(expr),
+                Box::new(self.parse_alternative()?),
+            )),
+            false => Ok(expr),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.eat(&Token::Ident("or".to_string())) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_comparison()?;
+        while self.eat(&Token::Ident("and".to_string())) {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_comparison()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr> {
+        let expr = self.parse_postfix()?;
+        match self.peek() {
+            Some(Token::Op(op)) if *op != "//" => {
+                let op = *op;
+                self.pos += 1;
+                Ok(Expr::Compare(
+                    Box::new(expr),
+                    op,
+                    Box::new(self.parse_postfix()?),
+                ))
+            }
+            _ => Ok(expr),
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_term()?;
+        loop {
+            if self.eat(&Token::Question) {
+                expr = Expr::Optional(Box::new(expr));
+            } else if self.eat(&Token::LBracket) {
+                expr = self.parse_brackets(expr)?;
+            } else if self.peek() == Some(&Token::Dot) {
+                match self.tokens.get(self.pos + 1) {
+                    Some(Token::Ident(_) | Token::Str(_) | Token::LBracket) => {
+                        self.pos += 1;
+                        expr = self.parse_path(expr)?;
+                    }
+                    _ => break,
+                }
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    /// Parse a field name or brackets following a `.`
+    fn parse_path(&mut self, target: Expr) -> Result<Expr> {
+        match self.peek().cloned() {
+            Some(Token::Ident(name) | Token::Str(name)) => {
+                self.pos += 1;
+                Ok(Expr::Index(
+                    Box::new(target),
+                    Box::new(Expr::Literal(Value::String(name))),
+                ))
+            }
+            Some(Token::LBracket) => {
+                self.pos += 1;
+                self.parse_brackets(target)
+            }
+            _ => Ok(target),
+        }
+    }
+
+    /// Parse an index or `[]` after its opening bracket
+    fn parse_brackets(&mut self, target: Expr) -> Result<Expr> {
+        if self.eat(&Token::RBracket) {
+            return Ok(Expr::Iterate(Box::new(target)));
+        }
+        let unsupported_slice = || invalid("slices such as `.[1:]` are not supported".to_string());
+        if self.peek() == Some(&Token::Colon) {
+            return Err(unsupported_slice());
+        }
+        let index = self.parse_pipe()?;
+        if self.peek() == Some(&Token::Colon) {
+            return Err(unsupported_slice());
+        }
+        self.expect(Token::RBracket)?;
+        Ok(Expr::Index(Box::new(target), Box::new(index)))
+    }
+
+    fn parse_term(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Dot) => self.parse_path(Expr::Identity),
+            Some(Token::Str(value)) => Ok(Expr::Literal(Value::String(value))),
+            Some(Token::Num(value)) => Ok(Expr::Literal(number(value))),
+            Some(Token::LParen) => {
+                let expr = self.parse_pipe()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Some(Token::LBracket) => {
+                if self.eat(&Token::RBracket) {
+                    return Ok(Expr::Array(None));
+                }
+                let expr = self.parse_pipe()?;
+                self.expect(Token::RBracket)?;
+                Ok(Expr::Array(Some(Box::new(expr))))
+            }
+            Some(Token::LBrace) => self.parse_object(),
+            Some(Token::Ident(name)) => match name.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                _ => {
+                    let mut args = vec![];
+                    if self.eat(&Token::LParen) {
+                        loop {
+                            args.push(self.parse_pipe()?);
+                            if !self.eat(&Token::Semicolon) {
+                                break;
+                            }
+                        }
+                        self.expect(Token::RParen)?;
+                    }
+                    if !FUNCTIONS.contains(&(name.as_str(), args.len())) {
+                        return Err(invalid(format!("unknown function {}/{}", name, args.len())));
+                    }
+                    Ok(Expr::Call(name, args))
+                }
+            },
+            Some(token) => Err(invalid(format!("unexpected {token}"))),
+            None => Err(invalid("unexpected end of query".to_string())),
+        }
+    }
+
+    /// Parse an object construction after its opening brace, eg
+    /// `{id, name: .metadata.name}`
+    fn parse_object(&mut self) -> Result<Expr> {
+        let mut entries = vec![];
+        if self.eat(&Token::RBrace) {
+            return Ok(Expr::Object(entries));
+        }
+
+        loop {
+            let key = match self.next() {
+                Some(Token::Ident(name) | Token::Str(name)) => Expr::Literal(Value::String(name)),
+                Some(Token::LParen) => {
+                    let key = self.parse_pipe()?;
+                    self.expect(Token::RParen)?;
+                    key
+                }
+                Some(token) => return Err(invalid(format!("unexpected {token} in object"))),
+                None => return Err(invalid("unterminated object".to_string())),
+            };
+            let value = match self.eat(&Token::Colon) {
+                true => self.parse_alternative()?,
+                false => Expr::Index(Box::new(Expr::Identity), Box::new(key.clone())),
+            };
+            entries.push((key, value));
+
+            if !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(Token::RBrace)?;
+        Ok(Expr::Object(entries))
+    }
+}
+
+/// Convert a number literal, keeping integers as integers
+fn number(value: f64) -> Value {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        return Value::from(value as i64);
+    }
+    Value::from(value)
+}
+
+fn eval(expr: &Expr, input: &Value) -> Result<Vec<Value>> {
+    match expr {
+        Expr::Identity => Ok(vec![input.clone()]),
+        Expr::Literal(value) => Ok(vec![value.clone()]),
+        Expr::Index(target, index) => {
+            let mut results = vec![];
+            for target in eval(target, input)? {
+                for index in eval(index, input)? {
+                    results.push(index_value(&target, &index)?);
+                }
+            }
+            Ok(results)
+        }
+        Expr::Iterate(target) => {
+            let mut results = vec![];
+            for target in eval(target, input)? {
+                results.extend(iterate(&target)?);
+            }
+            Ok(results)
+        }
+        Expr::Optional(expr) => Ok(eval(expr, input).unwrap_or_default()),
+        Expr::Pipe(left, right) => {
+            let mut results = vec![];
+            for value in eval(left, input)? {
+                results.extend(eval(right, &value)?);
+            }
+            Ok(results)
+        }
+        Expr::Comma(left, right) => {
+            let mut results = eval(left, input)?;
+            results.extend(eval(right, input)?);
+            Ok(results)
+        }
+        Expr::Alternative(left, right) => {
+            let values: Vec<Value> = eval(left, input)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(truthy)
+                .collect();
+            match values.is_empty() {
+                true => eval(right, input),
+                false => Ok(values),
+            }
+        }
+        Expr::Or(left, right) => logical(left, right, input, true),
+        Expr::And(left, right) => logical(left, right, input, false),
+        Expr::Compare(left, op, right) => {
+            let mut results = vec![];
+            for right in eval(right, input)? {
+                for left in eval(left, input)? {
+                    let ordering = compare(&left, &right);
+                    let result = match *op {
+                        "==" => ordering == Ordering::Equal,
+                        "!=" => ordering != Ordering::Equal,
+                        "<" => ordering == Ordering::Less,
+                        "<=" => ordering != Ordering::Greater,
+                        ">" => ordering == Ordering::Greater,
+                        _ => ordering != Ordering::Less,
+                    };
+                    results.push(Value::Bool(result));
+                }
+            }
+            Ok(results)
+        }
+        Expr::Array(None) => Ok(vec![Value::Array(vec![])]),
+        Expr::Array(Some(expr)) => Ok(vec![Value::Array(eval(expr, input)?)]),
+        Expr::Object(entries) => {
+            let mut objects = vec![Map::new()];
+            for (key, value) in entries {
+                let mut next = vec![];
+                for key in eval(key, input)? {
+                    let key = match key {
+                        Value::String(key) => key,
+                        other => {
+                            return Err(Error::msg(format!(
+                                "Object keys must be strings, not {}",
+                                type_name(&other)
+                            )))
+                        }
+                    };
+                    for value in eval(value, input)? {
+                        for object in &objects {
+                            let mut object = object.clone();
+                            object.insert(key.clone(), value.clone());
+                            next.push(object);
+                        }
+                    }
+                }
+                objects = next;
+            }
+            Ok(objects.into_iter().map(Value::Object).collect())
+        }
+        Expr::Call(name, args) => call(name, args, input),
+    }
+}
+
+fn logical(left: &Expr, right: &Expr, input: &Value, is_or: bool) -> Result<Vec<Value>> {
+    let mut results = vec![];
+    for left in eval(left, input)? {
+        if truthy(&left) == is_or {
+            results.push(Value::Bool(is_or));
+            continue;
+        }
+        for right in eval(right, input)? {
+            results.push(Value::Bool(truthy(&right)));
+        }
+    }
+    Ok(results)
+}
+
+fn index_value(target: &Value, index: &Value) -> Result<Value> {
+    match (target, index) {
+        (Value::Null, _) => Ok(Value::Null),
+        (Value::Object(map), Value::String(key)) => {
+            Ok(map.get(key).cloned().unwrap_or(Value::Null))
+        }
+        (Value::Array(items), Value::Number(n)) => {
+            let n = n.as_f64().unwrap_or_default() as i64;
+            let i = match n < 0 {
+                true => items.len() as i64 + n,
+                false => n,
+            };
+            Ok(usize::try_from(i)
+                .ok()
+                .and_then(|i| items.get(i))
+                .cloned()
+                .unwrap_or(Value::Null))
+        }
+        (target, index) => Err(Error::msg(format!(
+            "Cannot index {} with {}",
+            type_name(target),
+            match index {
+                Value::String(key) => format!("\"{key}\""),
+                other => type_name(other).to_string(),
+            }
+        ))),
+    }
+}
+
+fn iterate(value: &Value) -> Result<Vec<Value>> {
+    match value {
+        Value::Array(items) => Ok(items.clone()),
+        Value::Object(map) => Ok(map.values().cloned().collect()),
+        other => Err(Error::msg(format!(
+            "Cannot iterate over {}",
+            type_name(other)
+        ))),
+    }
+}
+
+fn call(name: &str, args: &[Expr], input: &Value) -> Result<Vec<Value>> {
+    let one = |value: Value| Ok(vec![value]);
+    match (name, args) {
+        ("empty", []) => Ok(vec![]),
+        ("not", []) => one(Value::Bool(!truthy(input))),
+        ("type", []) => one(Value::String(type_name(input).to_string())),
+        ("length", []) => one(match input {
+            Value::Null => Value::from(0),
+            Value::Bool(_) => return Err(Error::msg("boolean has no length")),
+            Value::Number(n) => Value::from(n.as_f64().unwrap_or_default().abs()),
+            Value::String(s) => Value::from(s.chars().count()),
+            Value::Array(items) => Value::from(items.len()),
+            Value::Object(map) => Value::from(map.len()),
+        }),
+        ("keys", []) => match input {
+            Value::Object(map) => {
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                one(Value::from_iter(keys.into_iter().cloned()))
+            }
+            Value::Array(items) => one(Value::from_iter(0..items.len())),
+            other => Err(Error::msg(format!("{} has no keys", type_name(other)))),
+        },
+        ("has", [key]) => {
+            let mut results = vec![];
+            for key in eval(key, input)? {
+                let has = match (input, &key) {
+                    (Value::Object(map), Value::String(key)) => map.contains_key(key),
+                    (Value::Array(items), Value::Number(n)) => n
+                        .as_f64()
+                        .is_some_and(|n| n >= 0.0 && (n as usize) < items.len()),
+                    (input, key) => {
+                        return Err(Error::msg(format!(
+                            "Cannot check whether {} has a {} key",
+                            type_name(input),
+                            type_name(key)
+                        )))
+                    }
+                };
+                results.push(Value::Bool(has));
+            }
+            Ok(results)
+        }
+        ("map", [f]) => {
+            let mut results = vec![];
+            for item in iterate(input)? {
+                results.extend(eval(f, &item)?);
+            }
+            one(Value::Array(results))
+        }
+        ("select", [f]) => Ok(eval(f, input)?
+            .iter()
+            .filter(|value| truthy(value))
+            .map(|_| input.clone())
+            .collect()),
+        ("first", []) => index_value(input, &Value::from(0)).map(|v| vec![v]),
+        ("last", []) => index_value(input, &Value::from(-1)).map(|v| vec![v]),
+        ("sort", []) => match input {
+            Value::Array(items) => {
+                let mut items = items.clone();
+                items.sort_by(compare);
+                one(Value::Array(items))
+            }
+            other => Err(Error::msg(format!("{} cannot be sorted", type_name(other)))),
+        },
+        ("add", []) => {
+            let mut sum: Option<Value> = None;
+            for item in iterate(input)? {
+                sum = Some(match sum {
+                    None => item,
+                    Some(sum) => add(sum, item)?,
+                });
+            }
+            one(sum.unwrap_or(Value::Null))
+        }
+        ("join", [separator]) => {
+            let mut results = vec![];
+            for separator in eval(separator, input)? {
+                let separator = match separator {
+                    Value::String(separator) => separator,
+                    other => {
+                        return Err(Error::msg(format!(
+                            "Cannot join with {}",
+                            type_name(&other)
+                        )))
+                    }
+                };
+                let parts: Vec<String> = iterate(input)?
+                    .iter()
+                    .map(|item| match item {
+                        Value::Null => String::new(),
+                        Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    })
+                    .collect();
+                results.push(Value::String(parts.join(&separator)));
+            }
+            Ok(results)
+        }
+        ("to_entries", []) => match input {
+            Value::Object(map) => one(Value::from_iter(map.iter().map(|(key, value)| {
+                let mut entry = Map::new();
+                entry.insert("key".to_string(), Value::String(key.clone()));
+                entry.insert("value".to_string(), value.clone());
+                Value::Object(entry)
+            }))),
+            other => Err(Error::msg(format!("{} has no entries", type_name(other)))),
+        },
+        ("tostring", []) => one(match input {
+            Value::String(s) => Value::String(s.clone()),
+            other => Value::String(other.to_string()),
+        }),
+        ("tonumber", []) => match input {
+            Value::Number(_) => one(input.clone()),
+            Value::String(s) => s
+                .trim()
+                .parse::<f64>()
+                .map(|n| vec![number(n)])
+                .map_err(|_| Error::msg(format!("Cannot parse {s:?} as a number"))),
+            other => Err(Error::msg(format!(
+                "{} cannot be converted to a number",
+                type_name(other)
+            ))),
+        },
+        (name, args) => Err(invalid(format!("unknown function {}/{}", name, args.len()))),
+    }
+}
+
+fn add(left: Value, right: Value) -> Result<Value> {
+    match (left, right) {
+        (Value::Null, value) | (value, Value::Null) => Ok(value),
+        (Value::Number(a), Value::Number(b)) => Ok(number(
+            a.as_f64().unwrap_or_default() + b.as_f64().unwrap_or_default(),
+        )),
+        (Value::String(a), Value::String(b)) => Ok(Value::String(a + &b)),
+        (Value::Array(mut a), Value::Array(b)) => {
+            a.extend(b);
+            Ok(Value::Array(a))
+        }
+        (Value::Object(mut a), Value::Object(b)) => {
+            a.extend(b);
+            Ok(Value::Object(a))
+        }
+        (a, b) => Err(Error::msg(format!(
+            "{} and {} cannot be added",
+            type_name(&a),
+            type_name(&b)
+        ))),
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    !matches!(value, Value::Null | Value::Bool(false))
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Order values the way jq does: null, false, true, numbers, strings,
+/// arrays and then objects
+fn compare(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Bool(false) => 1,
+            Value::Bool(true) => 2,
+            Value::Number(_) => 3,
+            Value::String(_) => 4,
+            Value::Array(_) => 5,
+            Value::Object(_) => 6,
+        }
+    }
+
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .unwrap_or_default()
+            .total_cmp(&b.as_f64().unwrap_or_default()),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Array(a), Value::Array(b)) => {
+            for (a, b) in a.iter().zip(b) {
+                match compare(a, b) {
+                    Ordering::Equal => continue,
+                    ordering => return ordering,
+                }
+            }
+            a.len().cmp(&b.len())
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            let mut a_keys: Vec<&String> = a.keys().collect();
+            let mut b_keys: Vec<&String> = b.keys().collect();
+            a_keys.sort();
+            b_keys.sort();
+            a_keys.cmp(&b_keys).then_with(|| {
+                a_keys
+                    .iter()
+                    .map(|key| compare(&a[*key], &b[*key]))
+                    .find(|ordering| *ordering != Ordering::Equal)
+                    .unwrap_or(Ordering::Equal)
+            })
+        }
+        (a, b) => rank(a).cmp(&rank(b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn run(query: &str, input: Value) -> Vec<Value> {
+        Query::parse(query).unwrap().run(&input).unwrap()
+    }
+
+    fn parse_error(query: &str) -> String {
+        Query::parse(query).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn paths() {
+        let input = json!({"a": {"b c": [1, 2, 3]}, "items": [{"id": 1}, {"id": 2}]});
+        assert_eq!(run(".", input.clone())[0], input);
+        assert_eq!(run(r#".a."b c""#, input.clone()), [json!([1, 2, 3])]);
+        assert_eq!(run(r#".a["b c"][0]"#, input.clone()), [json!(1)]);
+        assert_eq!(run(r#".a."b c"[-1]"#, input.clone()), [json!(3)]);
+        assert_eq!(run(".items[].id", input.clone()), [json!(1), json!(2)]);
+        assert_eq!(run(".missing.field", input.clone()), [Value::Null]);
+        assert_eq!(run(".a[]", input), [json!([1, 2, 3])]);
+    }
+
+    #[test]
+    fn pipes_and_commas() {
+        let input = json!({"id": 1, "name": "users", "fields": {"email": "pii"}});
+        assert_eq!(run(".id, .name", input.clone()), [json!(1), json!("users")]);
+        assert_eq!(run(".fields | keys", input.clone()), [json!(["email"])]);
+        assert_eq!(run("[.id, .name]", input.clone()), [json!([1, "users"])]);
+        assert_eq!(
+            run("{id, label: .fields.email}", input),
+            [json!({"id": 1, "label": "pii"})]
+        );
+    }
+
+    #[test]
+    fn operators() {
+        let input = json!({"a": 1, "b": null, "c": false});
+        assert_eq!(run(".a == 1", input.clone()), [json!(true)]);
+        assert_eq!(run(".a != 1", input.clone()), [json!(false)]);
+        assert_eq!(
+            run(".a < 2, .a <= 1, .a > 1, .a >= 1", input.clone()),
+            [json!(true), json!(true), json!(false), json!(true)]
+        );
+        assert_eq!(
+            run(r#"null < false, 1 < "a""#, input.clone()),
+            [json!(true), json!(true)]
+        );
+        assert_eq!(
+            run(".a and .c, .b or .a", input.clone()),
+            [json!(false), json!(true)]
+        );
+        assert_eq!(
+            run(r#".b // .c // "default""#, input.clone()),
+            [json!("default")]
+        );
+        assert_eq!(run(".a // 2", input.clone()), [json!(1)]);
+        assert_eq!(run(".a[0]?", input.clone()), Vec::<Value>::new());
+        assert_eq!(run("(.a, .b) | not", input), [json!(false), json!(true)]);
+    }
+
+    #[test]
+    fn functions() {
+        let input = json!([{"n": 3, "s": "c"}, {"n": 1, "s": "a"}, {"n": 2, "s": "b"}]);
+        assert_eq!(run("length", input.clone()), [json!(3)]);
+        assert_eq!(run("map(.n) | sort", input.clone()), [json!([1, 2, 3])]);
+        assert_eq!(run("map(.n) | add", input.clone()), [json!(6)]);
+        assert_eq!(
+            run("map(.s) | join(\"-\")", input.clone()),
+            [json!("c-a-b")]
+        );
+        assert_eq!(
+            run(".[] | select(.n > 1) | .s", input.clone()),
+            [json!("c"), json!("b")]
+        );
+        assert_eq!(
+            run("first.s, last.s", input.clone()),
+            [json!("c"), json!("b")]
+        );
+        assert_eq!(
+            run(".[0] | has(\"n\"), has(\"x\")", input.clone()),
+            [json!(true), json!(false)]
+        );
+        assert_eq!(
+            run(".[0] | to_entries | .[0]", input.clone()),
+            [json!({"key": "n", "value": 3})]
+        );
+        assert_eq!(
+            run("type, (.[0].n | tostring)", input.clone()),
+            [json!("array"), json!("3")]
+        );
+        assert_eq!(run(r#""42" | tonumber"#, input.clone()), [json!(42)]);
+        assert_eq!(run("empty", input), Vec::<Value>::new());
+    }
+
+    #[test]
+    fn runtime_errors() {
+        let error = |query: &str, input: Value| {
+            Query::parse(query)
+                .unwrap()
+                .run(&input)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(error(".a", json!([1])), "Cannot index array with \"a\"");
+        assert_eq!(error(".[]", json!(1)), "Cannot iterate over number");
+        assert_eq!(error("true | length", json!(null)), "boolean has no length");
+        assert_eq!(
+            error(r#""x" | tonumber"#, json!(null)),
+            "Cannot parse \"x\" as a number"
+        );
+        assert_eq!(
+            error("{(1): 2}", json!(null)),
+            "Object keys must be strings, not number"
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse_error(""), "Invalid query: unexpected end of query");
+        assert_eq!(
+            parse_error(".a |"),
+            "Invalid query: unexpected end of query"
+        );
+        assert_eq!(parse_error(".a)"), "Invalid query: unexpected `)`");
+        assert_eq!(parse_error("[.a"), "Invalid query: expected `]`");
+        assert_eq!(parse_error("(.a"), "Invalid query: expected `)`");
+        assert_eq!(parse_error(r#"."a"#), "Invalid query: unterminated string");
+        assert_eq!(parse_error("{a"), "Invalid query: expected `}`");
+        assert_eq!(
+            parse_error("{1: 2}"),
+            "Invalid query: unexpected number 1 in object"
+        );
+        assert_eq!(
+            parse_error("frobnicate(1)"),
+            "Invalid query: unknown function frobnicate/1"
+        );
+    }
+
+    #[test]
+    fn unsupported_syntax() {
+        assert_eq!(
+            parse_error(".[1:]"),
+            "Invalid query: slices such as `.[1:]` are not supported"
+        );
+        assert_eq!(
+            parse_error(".items[:2]"),
+            "Invalid query: slices such as `.[1:]` are not supported"
+        );
+        assert_eq!(
+            parse_error(".. | .id?"),
+            "Invalid query: recursive descent with `..` is not supported"
+        );
+        assert_eq!(
+            parse_error(".a % 2"),
+            "Invalid query: unexpected character `%`"
+        );
+        assert_eq!(parse_error("$x"), "Invalid query: unexpected character `$`");
+    }
+}
@@ -0,0 +1,1249 @@
+//! A subset of the Go `text/template` language, used to render command
+//! results with `--template`.
+//!
+//! Supported are actions with fields (`{{.a.b}}`), variables, literals,
+//! pipelines and function calls, `if`, `range` and `with` blocks with
+//! `else`, comments and `{{-`/`-}}` whitespace trimming. The functions are
+//! those of Go templates that apply to JSON values, plus `json`, `yaml`,
+//! `base64` and `urlencode`.
+
+use anyhow::{Error, Result};
+use base64::Engine;
+use serde_json::Value;
+use std::cmp::Ordering;
+
+/// A parsed template that can be rendered with JSON values
+#[derive(Clone, Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Template> {
+        let mut parser = Parser {
+            items: lex(source)?,
+            pos: 0,
+        };
+        match parser.parse_nodes()? {
+            (nodes, Stop::Eof) => Ok(Template { nodes }),
+            (_, Stop::End) => Err(invalid("unexpected {{end}}".to_string())),
+            (_, Stop::Else(_)) => Err(invalid("unexpected {{else}}".to_string())),
+        }
+    }
+
+    /// Render the template, with `data` as the initial value of `.` and `$`
+    pub fn render(&self, data: &Value) -> Result<String> {
+        let mut state = State {
+            root: data,
+            vars: vec![],
+            out: String::new(),
+        };
+        state.walk(data, &self.nodes)?;
+        Ok(state.out)
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::msg(format!("Invalid template: {message}"))
+}
+
+fn failed(message: String) -> Error {
+    Error::msg(format!("Template failed: {message}"))
+}
+
+// ------------------ Lexing ------------------ //
+
+enum Item {
+    Text(String),
+    Action(Vec<Token>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Dot,
+    Field(Vec<String>),
+    Var(String, Vec<String>),
+    Str(String),
+    Num(Value),
+    Ident(String),
+    Pipe,
+    LParen,
+    RParen,
+    Declare,
+    Assign,
+    Comma,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Dot => write!(f, "`.`"),
+            Token::Field(path) => write!(f, "`.{}`", path.join(".")),
+            Token::Var(name, _) => write!(f, "`${name}`"),
+            Token::Str(value) => write!(f, "string {value:?}"),
+            Token::Num(value) => write!(f, "number {value}"),
+            Token::Ident(name) => write!(f, "`{name}`"),
+            Token::Pipe => write!(f, "`|`"),
+            Token::LParen => write!(f, "`(`"),
+            Token::RParen => write!(f, "`)`"),
+            Token::Declare => write!(f, "`:=`"),
+            Token::Assign => write!(f, "`=`"),
+            Token::Comma => write!(f, "`,`"),
+        }
+    }
+}
+
+/// Split a template into text and the tokens of each action
+fn lex(source: &str) -> Result<Vec<Item>> {
+    let mut items = vec![];
+    let mut rest = source;
+    let mut trim_next = false;
+
+    loop {
+        let start = rest.find("{{").unwrap_or(rest.len());
+        let mut text = &rest[..start];
+        if trim_next {
+            text = text.trim_start();
+        }
+
+        let Some(action) = rest.get(start + 2..) else {
+            if !text.is_empty() {
+                items.push(Item::Text(text.to_string()));
+            }
+            return Ok(items);
+        };
+        let action = match action.strip_prefix('-') {
+            Some(action) if action.starts_with(char::is_whitespace) => {
+                text = text.trim_end();
+                action
+            }
+            _ => action,
+        };
+        if !text.is_empty() {
+            items.push(Item::Text(text.to_string()));
+        }
+
+        let end = close(action)?;
+        let mut inner = &action[..end];
+        trim_next = false;
+        if let Some(trimmed) = inner.strip_suffix('-') {
+            if trimmed.ends_with(char::is_whitespace) {
+                inner = trimmed;
+                trim_next = true;
+            }
+        }
+        let inner = inner.trim();
+        if !(inner.starts_with("/*") && inner.ends_with("*/")) {
+            items.push(Item::Action(tokenize(inner)?));
+        }
+        rest = &action[end + 2..];
+    }
+}
+
+/// The position of the `}}` closing an action, skipping quoted strings
+fn close(action: &str) -> Result<usize> {
+    let bytes = action.as_bytes();
+    let mut quote = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        match (quote, bytes[i]) {
+            (Some(b'"'), b'\\') => i += 1,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, b'"' | b'`') => quote = Some(bytes[i]),
+            (None, b'}') if bytes.get(i + 1) == Some(&b'}') => return Ok(i),
+            (None, _) => (),
+        }
+        i += 1;
+    }
+    Err(invalid("unclosed action, missing `}}`".to_string()))
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let token = match c {
+            '|' => Token::Pipe,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            '=' => Token::Assign,
+            ':' if chars.get(i + 1) == Some(&'=') => {
+                i += 2;
+                tokens.push(Token::Declare);
+                continue;
+            }
+            '.' if chars.get(i + 1) == Some(&'.') => {
+                return Err(invalid("`..` is not supported".to_string()))
+            }
+            '.' if chars.get(i + 1).is_some_and(|c| is_ident(*c)) => {
+                let (path, end) = fields(&chars, i);
+                i = end;
+                tokens.push(Token::Field(path));
+                continue;
+            }
+            '.' => Token::Dot,
+            '$' => {
+                let start = i + 1;
+                let mut end = start;
+                while end < chars.len() && is_ident(chars[end]) {
+                    end += 1;
+                }
+                let name: String = chars[start..end].iter().collect();
+                let (path, end) = fields(&chars, end);
+                i = end;
+                tokens.push(Token::Var(name, path));
+                continue;
+            }
+            '"' => {
+                let (value, end) = string(&chars, i + 1)?;
+                i = end;
+                tokens.push(Token::Str(value));
+                continue;
+            }
+            '`' => {
+                let end = (i + 1..chars.len())
+                    .find(|j| chars[*j] == '`')
+                    .ok_or_else(|| invalid("unterminated raw string".to_string()))?;
+                tokens.push(Token::Str(chars[i + 1..end].iter().collect()));
+                i = end + 1;
+                continue;
+            }
+            c if c.is_ascii_digit()
+                || (c == '-' && chars.get(i + 1).is_some_and(char::is_ascii_digit)) =>
+            {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let number = match text.parse::<i64>() {
+                    Ok(n) => Value::from(n),
+                    Err(_) => text
+                        .parse::<f64>()
+                        .ok()
+                        .and_then(|n| serde_json::Number::from_f64(n).map(Value::Number))
+                        .ok_or_else(|| invalid(format!("invalid number {text}")))?,
+                };
+                tokens.push(Token::Num(number));
+                continue;
+            }
+            c if is_ident(c) => {
+                let start = i;
+                while i < chars.len() && is_ident(chars[i]) {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+                continue;
+            }
+            other => return Err(invalid(format!("unexpected character {other:?}"))),
+        };
+        tokens.push(token);
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+/// A chain of field names such as `.a.b`, starting at a `.`
+fn fields(chars: &[char], mut i: usize) -> (Vec<String>, usize) {
+    let mut path = vec![];
+    while chars.get(i) == Some(&'.') && chars.get(i + 1).is_some_and(|c| is_ident(*c)) {
+        let start = i + 1;
+        i = start;
+        while i < chars.len() && is_ident(chars[i]) {
+            i += 1;
+        }
+        path.push(chars[start..i].iter().collect());
+    }
+    (path, i)
+}
+
+/// Parse a double quoted string starting after the opening quote,
+/// returning it and the position after the closing quote
+fn string(chars: &[char], start: usize) -> Result<(String, usize)> {
+    let mut value = String::new();
+    let mut i = start;
+    while i < chars.len() {
+        match chars[i] {
+            '"' => return Ok((value, i + 1)),
+            '\\' => {
+                i += 1;
+                match chars.get(i) {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('r') => value.push('\r'),
+                    Some(c @ ('"' | '\\')) => value.push(*c),
+                    Some(c) => return Err(invalid(format!("unknown escape \\{c}"))),
+                    None => break,
+                }
+            }
+            c => value.push(c),
+        }
+        i += 1;
+    }
+    Err(invalid("unterminated string".to_string()))
+}
+
+// ------------------ Parsing ------------------ //
+
+#[derive(Clone, Debug)]
+enum Node {
+    Text(String),
+    Action(Pipeline),
+    If {
+        cond: Pipeline,
+        body: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    Range {
+        pipe: Pipeline,
+        body: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    With {
+        pipe: Pipeline,
+        body: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+/// Commands joined by `|`, optionally assigned to variables
+#[derive(Clone, Debug)]
+struct Pipeline {
+    vars: Vec<String>,
+    assign: bool,
+    commands: Vec<Command>,
+}
+
+/// A value, or a function and its arguments
+#[derive(Clone, Debug)]
+struct Command {
+    args: Vec<Arg>,
+}
+
+#[derive(Clone, Debug)]
+enum Arg {
+    Dot,
+    Field(Vec<String>),
+    Var(String, Vec<String>),
+    Literal(Value),
+    Function(String),
+    Pipeline(Box<Pipeline>),
+}
+
+/// The action that ended a list of nodes
+enum Stop {
+    End,
+    Else(Vec<Token>),
+    Eof,
+}
+
+struct Parser {
+    items: Vec<Item>,
+    pos: usize,
+}
+
+impl Parser {
+    fn parse_nodes(&mut self) -> Result<(Vec<Node>, Stop)> {
+        let mut nodes = vec![];
+        while let Some(item) = self.items.get_mut(self.pos) {
+            self.pos += 1;
+            let tokens = match item {
+                Item::Text(text) => {
+                    nodes.push(Node::Text(std::mem::take(text)));
+                    continue;
+                }
+                Item::Action(tokens) => std::mem::take(tokens),
+            };
+
+            let node = match tokens.first() {
+                Some(Token::Ident(keyword)) => match keyword.as_str() {
+                    "end" if tokens.len() == 1 => return Ok((nodes, Stop::End)),
+                    "end" => return Err(invalid(format!("unexpected {} after end", tokens[1]))),
+                    "else" => return Ok((nodes, Stop::Else(tokens[1..].to_vec()))),
+                    "if" => self.parse_if(&tokens[1..])?,
+                    "range" => self.parse_block("range", &tokens[1..])?,
+                    "with" => self.parse_block("with", &tokens[1..])?,
+                    _ => Node::Action(pipeline(&tokens)?),
+                },
+                Some(_) => Node::Action(pipeline(&tokens)?),
+                None => return Err(invalid("empty action".to_string())),
+            };
+            nodes.push(node);
+        }
+        Ok((nodes, Stop::Eof))
+    }
+
+    fn parse_if(&mut self, tokens: &[Token]) -> Result<Node> {
+        let cond = pipeline(tokens)?;
+        let (body, stop) = self.parse_nodes()?;
+        Ok(Node::If {
+            cond,
+            body,
+            otherwise: self.parse_else("if", stop)?,
+        })
+    }
+
+    fn parse_block(&mut self, keyword: &str, tokens: &[Token]) -> Result<Node> {
+        let pipe = pipeline(tokens)?;
+        let (body, stop) = self.parse_nodes()?;
+        let otherwise = self.parse_else(keyword, stop)?;
+        Ok(match keyword {
+            "range" => Node::Range {
+                pipe,
+                body,
+                otherwise,
+            },
+            _ => Node::With {
+                pipe,
+                body,
+                otherwise,
+            },
+        })
+    }
+
+    /// Parse the `else` branch of a block, if it has one. `else if` and
+    /// `else with` start a nested block that ends with the same `end`.
+    fn parse_else(&mut self, keyword: &str, stop: Stop) -> Result<Vec<Node>> {
+        match stop {
+            Stop::End => Ok(vec![]),
+            Stop::Else(tokens) if tokens.is_empty() => match self.parse_nodes()? {
+                (nodes, Stop::End) => Ok(nodes),
+                (_, Stop::Else(_)) => {
+                    Err(invalid(format!("{{{{{keyword}}}}} has two {{{{else}}}}")))
+                }
+                (_, Stop::Eof) => Err(invalid(format!("{{{{{keyword}}}}} is missing {{{{end}}}}"))),
+            },
+            Stop::Else(tokens) => match &tokens[0] {
+                Token::Ident(nested) if nested == "if" => Ok(vec![self.parse_if(&tokens[1..])?]),
+                Token::Ident(nested) if nested == "with" => {
+                    Ok(vec![self.parse_block("with", &tokens[1..])?])
+                }
+                token => Err(invalid(format!("unexpected {token} after else"))),
+            },
+            Stop::Eof => Err(invalid(format!("{{{{{keyword}}}}} is missing {{{{end}}}}"))),
+        }
+    }
+}
+
+/// Parse a pipeline, with the variables it declares or assigns
+fn pipeline(tokens: &[Token]) -> Result<Pipeline> {
+    let mut vars = vec![];
+    let mut assign = false;
+    let declaration = tokens
+        .iter()
+        .position(|t| *t == Token::Declare || *t == Token::Assign);
+    let mut rest = tokens;
+    if let Some(position) = declaration {
+        let names = &tokens[..position];
+        for (i, token) in names.iter().enumerate() {
+            match token {
+                Token::Var(name, path) if i % 2 == 0 && path.is_empty() => vars.push(name.clone()),
+                Token::Comma if i % 2 == 1 => (),
+                token => return Err(invalid(format!("unexpected {token} in declaration"))),
+            }
+        }
+        if vars.is_empty() || vars.len() > 2 || names.len() != vars.len() * 2 - 1 {
+            return Err(invalid("invalid variable declaration".to_string()));
+        }
+        assign = tokens[position] == Token::Assign;
+        rest = &tokens[position + 1..];
+    }
+
+    let mut commands = vec![];
+    let mut args = vec![];
+    let mut i = 0;
+    while i < rest.len() {
+        let arg = match &rest[i] {
+            Token::Pipe => {
+                if args.is_empty() {
+                    return Err(invalid("missing command before `|`".to_string()));
+                }
+                commands.push(command(std::mem::take(&mut args), commands.len())?);
+                i += 1;
+                continue;
+            }
+            Token::LParen => {
+                let end = matching_paren(rest, i)?;
+                let arg = Arg::Pipeline(Box::new(pipeline(&rest[i + 1..end])?));
+                i = end + 1;
+                args.push(arg);
+                continue;
+            }
+            Token::Dot => Arg::Dot,
+            Token::Field(path) => Arg::Field(path.clone()),
+            Token::Var(name, path) => Arg::Var(name.clone(), path.clone()),
+            Token::Str(value) => Arg::Literal(Value::String(value.clone())),
+            Token::Num(value) => Arg::Literal(value.clone()),
+            Token::Ident(name) => match name.as_str() {
+                "true" => Arg::Literal(Value::Bool(true)),
+                "false" => Arg::Literal(Value::Bool(false)),
+                "nil" => Arg::Literal(Value::Null),
+                name if FUNCTIONS.contains(&name) => Arg::Function(name.to_string()),
+                name => return Err(invalid(format!("function {name:?} not defined"))),
+            },
+            token => return Err(invalid(format!("unexpected {token}"))),
+        };
+        args.push(arg);
+        i += 1;
+    }
+    if args.is_empty() {
+        return Err(invalid("missing value".to_string()));
+    }
+    commands.push(command(args, commands.len())?);
+
+    Ok(Pipeline {
+        vars,
+        assign,
+        commands,
+    })
+}
+
+/// Check the arguments of the command at `position` in a pipeline. Only
+/// functions can be given arguments, including the piped value.
+fn command(args: Vec<Arg>, position: usize) -> Result<Command> {
+    let is_function = matches!(args[0], Arg::Function(_));
+    if !is_function && args.len() > 1 {
+        return Err(invalid(
+            "can't give an argument to a value that isn't a function".to_string(),
+        ));
+    }
+    if !is_function && position > 0 {
+        return Err(invalid("only functions can be used after `|`".to_string()));
+    }
+    Ok(Command { args })
+}
+
+fn matching_paren(tokens: &[Token], open: usize) -> Result<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::LParen => depth += 1,
+            Token::RParen if depth == 1 => return Ok(i),
+            Token::RParen => depth -= 1,
+            _ => (),
+        }
+    }
+    Err(invalid("unclosed `(`".to_string()))
+}
+
+// ------------------ Rendering ------------------ //
+
+/// Functions that can be called in templates
+pub const FUNCTIONS: [&str; 18] = [
+    "and",
+    "or",
+    "not",
+    "len",
+    "index",
+    "eq",
+    "ne",
+    "lt",
+    "le",
+    "gt",
+    "ge",
+    "print",
+    "println",
+    "printf",
+    "json",
+    "yaml",
+    "base64",
+    "urlencode",
+];
+
+struct State<'a> {
+    root: &'a Value,
+    vars: Vec<(String, Value)>,
+    out: String,
+}
+
+impl State<'_> {
+    fn walk(&mut self, dot: &Value, nodes: &[Node]) -> Result<()> {
+        for node in nodes {
+            let scope = self.vars.len();
+            match node {
+                Node::Text(text) => self.out.push_str(text),
+                Node::Action(pipe) => {
+                    let value = self.eval_pipeline(dot, pipe)?;
+                    if pipe.vars.is_empty() {
+                        self.out.push_str(&text(&value));
+                    }
+                    // Variables declared by an action last until the end of the block
+                    continue;
+                }
+                Node::If {
+                    cond,
+                    body,
+                    otherwise,
+                } => match truthy(&self.eval_pipeline(dot, cond)?) {
+                    true => self.walk(dot, body)?,
+                    false => self.walk(dot, otherwise)?,
+                },
+                Node::With {
+                    pipe,
+                    body,
+                    otherwise,
+                } => {
+                    let value = self.eval_pipeline(dot, pipe)?;
+                    match truthy(&value) {
+                        true => self.walk(&value, body)?,
+                        false => self.walk(dot, otherwise)?,
+                    }
+                }
+                Node::Range {
+                    pipe,
+                    body,
+                    otherwise,
+                } => {
+                    let value = self.eval_commands(dot, &pipe.commands)?;
+                    let items: Vec<(Value, Value)> = match value {
+                        Value::Array(items) => items
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, item)| (Value::from(i), item))
+                            .collect(),
+                        Value::Object(map) => map
+                            .into_iter()
+                            .map(|(key, item)| (Value::String(key), item))
+                            .collect(),
+                        Value::Null => vec![],
+                        other => {
+                            return Err(failed(format!("can't range over {}", type_name(&other))))
+                        }
+                    };
+                    if items.is_empty() {
+                        self.walk(dot, otherwise)?;
+                    }
+                    for (key, item) in items {
+                        self.vars.truncate(scope);
+                        match pipe.vars.as_slice() {
+                            [value] => self.vars.push((value.clone(), item.clone())),
+                            [index, value] => {
+                                self.vars.push((index.clone(), key));
+                                self.vars.push((value.clone(), item.clone()));
+                            }
+                            _ => (),
+                        }
+                        self.walk(&item, body)?;
+                    }
+                }
+            }
+            self.vars.truncate(scope);
+        }
+        Ok(())
+    }
+
+    fn eval_pipeline(&mut self, dot: &Value, pipe: &Pipeline) -> Result<Value> {
+        let value = self.eval_commands(dot, &pipe.commands)?;
+        for name in &pipe.vars {
+            match pipe.assign {
+                true => {
+                    let var = self
+                        .vars
+                        .iter_mut()
+                        .rev()
+                        .find(|(var, _)| var == name)
+                        .ok_or_else(|| failed(format!("undefined variable ${name}")))?;
+                    var.1 = value.clone();
+                }
+                false => self.vars.push((name.clone(), value.clone())),
+            }
+        }
+        Ok(value)
+    }
+
+    fn eval_commands(&mut self, dot: &Value, commands: &[Command]) -> Result<Value> {
+        let mut piped = None;
+        for command in commands {
+            piped = Some(self.eval_command(dot, command, piped)?);
+        }
+        Ok(piped.unwrap_or(Value::Null))
+    }
+
+    fn eval_command(
+        &mut self,
+        dot: &Value,
+        command: &Command,
+        piped: Option<Value>,
+    ) -> Result<Value> {
+        let (first, rest) = command
+            .args
+            .split_first()
+            .ok_or_else(|| failed("empty command".to_string()))?;
+        match first {
+            Arg::Function(name) => {
+                let mut args = rest
+                    .iter()
+                    .map(|arg| self.eval_arg(dot, arg))
+                    .collect::<Result<Vec<_>>>()?;
+                args.extend(piped);
+                call(name, args)
+            }
+            _ if !rest.is_empty() || piped.is_some() => Err(failed(
+                "can't give an argument to a value that isn't a function".to_string(),
+            )),
+            arg => self.eval_arg(dot, arg),
+        }
+    }
+
+    fn eval_arg(&mut self, dot: &Value, arg: &Arg) -> Result<Value> {
+        match arg {
+            Arg::Dot => Ok(dot.clone()),
+            Arg::Field(path) => field(dot, path),
+            Arg::Var(name, path) => {
+                let value = match name.as_str() {
+                    "" => self.root,
+                    name => self
+                        .vars
+                        .iter()
+                        .rev()
+                        .find(|(var, _)| var == name)
+                        .map(|(_, value)| value)
+                        .ok_or_else(|| failed(format!("undefined variable ${name}")))?,
+                };
+                field(value, path)
+            }
+            Arg::Literal(value) => Ok(value.clone()),
+            Arg::Function(name) => call(name, vec![]),
+            Arg::Pipeline(pipe) => self.eval_pipeline(dot, pipe),
+        }
+    }
+}
+
+/// Look up a chain of fields. Missing fields are null.
+fn field(value: &Value, path: &[String]) -> Result<Value> {
+    let mut value = value;
+    for name in path {
+        value = match value {
+            Value::Object(map) => map.get(name).unwrap_or(&Value::Null),
+            Value::Null => &Value::Null,
+            other => {
+                return Err(failed(format!(
+                    "can't get field {name} of {}",
+                    type_name(other)
+                )))
+            }
+        };
+    }
+    Ok(value.clone())
+}
+
+fn call(name: &str, args: Vec<Value>) -> Result<Value> {
+    let arity = |count: usize| match args.len() == count {
+        true => Ok(()),
+        false => Err(failed(format!(
+            "{name} takes {count} arguments, not {}",
+            args.len()
+        ))),
+    };
+
+    match name {
+        "and" => Ok(args
+            .iter()
+            .find(|arg| !truthy(arg))
+            .or(args.last())
+            .cloned()
+            .unwrap_or(Value::Null)),
+        "or" => Ok(args
+            .iter()
+            .find(|arg| truthy(arg))
+            .or(args.last())
+            .cloned()
+            .unwrap_or(Value::Null)),
+        "not" => {
+            arity(1)?;
+            Ok(Value::Bool(!truthy(&args[0])))
+        }
+        "len" => {
+            arity(1)?;
+            let len = match &args[0] {
+                Value::String(s) => s.chars().count(),
+                Value::Array(items) => items.len(),
+                Value::Object(map) => map.len(),
+                other => return Err(failed(format!("{} has no length", type_name(other)))),
+            };
+            Ok(Value::from(len))
+        }
+        "index" => {
+            let (target, keys) = args
+                .split_first()
+                .ok_or_else(|| failed("index needs a value".to_string()))?;
+            keys.iter()
+                .try_fold(target.clone(), |target, key| index(&target, key))
+        }
+        "eq" => {
+            let (first, rest) = args
+                .split_first()
+                .ok_or_else(|| failed("eq needs arguments".to_string()))?;
+            Ok(Value::Bool(rest.iter().any(|arg| equal(first, arg))))
+        }
+        "ne" => {
+            arity(2)?;
+            Ok(Value::Bool(!equal(&args[0], &args[1])))
+        }
+        "lt" | "le" | "gt" | "ge" => {
+            arity(2)?;
+            let ordering = compare(&args[0], &args[1])?;
+            Ok(Value::Bool(match name {
+                "lt" => ordering == Ordering::Less,
+                "le" => ordering != Ordering::Greater,
+                "gt" => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            }))
+        }
+        "print" => {
+            let mut out = String::new();
+            for (i, arg) in args.iter().enumerate() {
+                // Like Go, operands are separated by a space when neither is a string
+                if i > 0 && !args[i - 1].is_string() && !arg.is_string() {
+                    out.push(' ');
+                }
+                out.push_str(&text(arg));
+            }
+            Ok(Value::String(out))
+        }
+        "println" => {
+            let parts: Vec<String> = args.iter().map(text).collect();
+            Ok(Value::String(format!("{}\n", parts.join(" "))))
+        }
+        "printf" => {
+            let (format, args) = args
+                .split_first()
+                .ok_or_else(|| failed("printf needs a format".to_string()))?;
+            let format = format
+                .as_str()
+                .ok_or_else(|| failed("the printf format must be a string".to_string()))?;
+            Ok(Value::String(printf(format, args)?))
+        }
+        "json" => {
+            arity(1)?;
+            Ok(Value::String(serde_json::to_string(&args[0])?))
+        }
+        "yaml" => {
+            arity(1)?;
+            let yaml = serde_yaml::to_string(&args[0])?;
+            Ok(Value::String(yaml.trim_end_matches('\n').to_string()))
+        }
+        "base64" => {
+            arity(1)?;
+            let encoded = base64::engine::general_purpose::STANDARD.encode(text(&args[0]));
+            Ok(Value::String(encoded))
+        }
+        "urlencode" => {
+            arity(1)?;
+            Ok(Value::String(
+                urlencoding::encode(&text(&args[0])).into_owned(),
+            ))
+        }
+        name => Err(failed(format!("function {name:?} not defined"))),
+    }
+}
+
+fn index(target: &Value, key: &Value) -> Result<Value> {
+    match (target, key) {
+        (Value::Array(items), Value::Number(n)) => {
+            let i = n
+                .as_u64()
+                .ok_or_else(|| failed(format!("invalid index {n}")))?;
+            items
+                .get(i as usize)
+                .cloned()
+                .ok_or_else(|| failed(format!("index {i} out of range")))
+        }
+        (Value::Object(map), Value::String(key)) => {
+            Ok(map.get(key).cloned().unwrap_or(Value::Null))
+        }
+        (Value::Null, _) => Ok(Value::Null),
+        (target, key) => Err(failed(format!(
+            "can't index {} with {}",
+            type_name(target),
+            type_name(key)
+        ))),
+    }
+}
+
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (a, b) => a == b,
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Result<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .zip(b.as_f64())
+            .and_then(|(a, b)| a.partial_cmp(&b))
+            .ok_or_else(|| failed("can't compare numbers".to_string())),
+        (Value::String(a), Value::String(b)) => Ok(a.cmp(b)),
+        (a, b) => Err(failed(format!(
+            "can't compare {} with {}",
+            type_name(a),
+            type_name(b)
+        ))),
+    }
+}
+
+/// Format values like Go's `fmt.Sprintf`, supporting the `%v`, `%s`, `%d`,
+/// `%f`, `%q` and `%t` verbs with flags, width and precision
+fn printf(format: &str, args: &[Value]) -> Result<String> {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+
+        let (mut left, mut zero) = (false, false);
+        while let Some(flag) = chars.next_if(|c| matches!(c, '-' | '0' | '+' | ' ' | '#')) {
+            match flag {
+                '-' => left = true,
+                '0' => zero = true,
+                _ => (),
+            }
+        }
+        let mut width = String::new();
+        while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+            width.push(digit);
+        }
+        let mut precision = None;
+        if chars.next_if_eq(&'.').is_some() {
+            let mut digits = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            precision = Some(digits.parse().unwrap_or(0));
+        }
+
+        let Some(verb) = chars.next() else {
+            out.push_str("%!(NOVERB)");
+            break;
+        };
+        if verb == '%' {
+            out.push('%');
+            continue;
+        }
+        if !matches!(verb, 'v' | 's' | 'd' | 'f' | 'q' | 't') {
+            return Err(failed(format!("printf verb %{verb} is not supported")));
+        }
+        let Some(arg) = args.next() else {
+            out.push_str(&format!("%!{verb}(MISSING)"));
+            continue;
+        };
+
+        let formatted = match (verb, arg) {
+            ('d', Value::Number(n)) => n
+                .as_i64()
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| format!("{:.0}", n.as_f64().unwrap_or_default())),
+            ('f', Value::Number(n)) => {
+                format!(
+                    "{:.*}",
+                    precision.unwrap_or(6),
+                    n.as_f64().unwrap_or_default()
+                )
+            }
+            ('q', arg) => serde_json::to_string(&text(arg)).unwrap_or_default(),
+            ('s' | 'v' | 't', arg) => {
+                let text = text(arg);
+                match precision {
+                    Some(precision) => text.chars().take(precision).collect(),
+                    None => text,
+                }
+            }
+            (verb, arg) => format!("%!{verb}({})", text(arg)),
+        };
+
+        let width = width.parse().unwrap_or(0);
+        let padded = match (left, zero && arg.is_number()) {
+            (true, _) => format!("{formatted:<width$}"),
+            (false, true) => format!("{formatted:0>width$}"),
+            (false, false) => format!("{formatted:>width$}"),
+        };
+        out.push_str(&padded);
+    }
+
+    Ok(out)
+}
+
+/// The text of a value when printed. Null prints nothing, and arrays and
+/// objects are printed as compact JSON.
+fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Whether a value is true in `if`, `with`, `and`, `or` and `not`, as in Go
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &str, data: Value) -> String {
+        Template::parse(template).unwrap().render(&data).unwrap()
+    }
+
+    fn parse_error(template: &str) -> String {
+        Template::parse(template).unwrap_err().to_string()
+    }
+
+    fn render_error(template: &str, data: Value) -> String {
+        Template::parse(template)
+            .unwrap()
+            .render(&data)
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn fields_and_literals() {
+        let data = json!({"a": {"b": "x"}, "n": 4, "list": [1, 2], "none": null});
+        assert_eq!(render("a {{.a.b}} {{.n}}", data.clone()), "a x 4");
+        assert_eq!(
+            render("{{.list}} {{.a}}", data.clone()),
+            r#"[1,2] {"b":"x"}"#
+        );
+        assert_eq!(render("[{{.none}}{{.missing.field}}]", data.clone()), "[]");
+        assert_eq!(
+            render(r#"{{"q\"\n"}}{{`raw\n`}} {{1.5}} {{-2}} {{true}}"#, data),
+            "q\"\nraw\\n 1.5 -2 true"
+        );
+    }
+
+    #[test]
+    fn comments_and_trimming() {
+        assert_eq!(
+            render("a  {{- /* hidden */ -}}  b\n{{- .x }}", json!({"x": 1})),
+            "ab1"
+        );
+        assert_eq!(render("a {{- 1}} {{2 -}} b", json!(null)), "a1 2b");
+    }
+
+    #[test]
+    fn variables() {
+        let data = json!({"a": 1, "b": 2});
+        assert_eq!(
+            render("{{$x := .a}}{{$x}}{{$x = .b}}{{$x}}{{$.a}}", data.clone()),
+            "121"
+        );
+        assert_eq!(render("{{with .b}}{{$y := .}}{{$y}}{{end}}", data), "2");
+    }
+
+    #[test]
+    fn blocks() {
+        let data = json!({"items": [{"id": "a"}, {"id": "b"}], "map": {"k": 1}, "empty": []});
+        assert_eq!(
+            render("{{range .items}}{{.id}},{{end}}", data.clone()),
+            "a,b,"
+        );
+        assert_eq!(
+            render(
+                "{{range $i, $v := .items}}{{$i}}={{$v.id}} {{end}}",
+                data.clone()
+            ),
+            "0=a 1=b "
+        );
+        assert_eq!(
+            render("{{range $k, $v := .map}}{{$k}}:{{$v}}{{end}}", data.clone()),
+            "k:1"
+        );
+        assert_eq!(
+            render("{{range .empty}}x{{else}}none{{end}}", data.clone()),
+            "none"
+        );
+        assert_eq!(
+            render(
+                "{{if .missing}}a{{else if .empty}}b{{else}}c{{end}}",
+                data.clone()
+            ),
+            "c"
+        );
+        assert_eq!(render("{{with .map}}{{.k}}{{else}}none{{end}}", data), "1");
+    }
+
+    #[test]
+    fn functions() {
+        let data = json!({"s": "a b", "n": 2, "list": ["x", "y"], "obj": {"k": "v"}});
+        assert_eq!(render("{{len .s}} {{len .list}}", data.clone()), "3 2");
+        assert_eq!(
+            render("{{index .list 1}} {{index .obj \"k\"}}", data.clone()),
+            "y v"
+        );
+        assert_eq!(
+            render(
+                "{{eq .n 1 2}} {{ne .n 2}} {{lt .n 3}} {{ge .s \"b\"}}",
+                data.clone()
+            ),
+            "true false true false"
+        );
+        assert_eq!(
+            render("{{and .n .s}} {{or .missing .n}} {{not .n}}", data.clone()),
+            "a b 2 false"
+        );
+        assert_eq!(
+            render("{{print .n 3 \"x\"}}|{{println .n .s}}", data.clone()),
+            "2 3x|2 a b\n"
+        );
+        assert_eq!(
+            render(
+                r#"{{printf "%-4s|%05.1f|%3d|%q|%v|%t|%%" .s 3.14159 .n .s .list true}}"#,
+                data.clone()
+            ),
+            "a b |003.1|  2|\"a b\"|[\"x\",\"y\"]|true|%"
+        );
+        assert_eq!(
+            render(
+                "{{.obj | json}} {{.s | base64}} {{.s | urlencode}}",
+                data.clone()
+            ),
+            r#"{"k":"v"} YSBi a%20b"#
+        );
+        assert_eq!(render("{{yaml .obj}}", data.clone()), "k: v");
+        assert_eq!(
+            render("{{.list | len | printf \"%d items\"}}", data),
+            "2 items"
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            parse_error("{{.a"),
+            "Invalid template: unclosed action, missing `}}`"
+        );
+        assert_eq!(parse_error("{{}}"), "Invalid template: empty action");
+        assert_eq!(
+            parse_error("{{end}}"),
+            "Invalid template: unexpected {{end}}"
+        );
+        assert_eq!(
+            parse_error("{{if .a}}x"),
+            "Invalid template: {{if}} is missing {{end}}"
+        );
+        assert_eq!(
+            parse_error("{{range .a}}{{else}}{{else}}{{end}}"),
+            "Invalid template: {{range}} has two {{else}}"
+        );
+        assert_eq!(
+            parse_error("{{frobnicate .a}}"),
+            "Invalid template: function \"frobnicate\" not defined"
+        );
+        assert_eq!(
+            parse_error(r#"{{"a}}"#),
+            "Invalid template: unclosed action, missing `}}`"
+        );
+        assert_eq!(
+            parse_error(r#"{{"\x"}}"#),
+            "Invalid template: unknown escape \\x"
+        );
+        assert_eq!(parse_error("{{(len .a}}"), "Invalid template: unclosed `(`");
+        assert_eq!(
+            parse_error("{{| len}}"),
+            "Invalid template: missing command before `|`"
+        );
+        assert_eq!(
+            parse_error("{{$a, $b, $c := .}}"),
+            "Invalid template: invalid variable declaration"
+        );
+        assert_eq!(
+            parse_error("{{.a .b}}"),
+            "Invalid template: can't give an argument to a value that isn't a function"
+        );
+        assert_eq!(
+            parse_error("{{.a | .b}}"),
+            "Invalid template: only functions can be used after `|`"
+        );
+    }
+
+    #[test]
+    fn render_errors() {
+        assert_eq!(
+            render_error("{{.a.b}}", json!({"a": 1})),
+            "Template failed: can't get field b of number"
+        );
+        assert_eq!(
+            render_error("{{range .}}{{end}}", json!(1)),
+            "Template failed: can't range over number"
+        );
+        assert_eq!(
+            render_error("{{$x = 1}}", json!(null)),
+            "Template failed: undefined variable $x"
+        );
+        assert_eq!(
+            render_error("{{lt 1 \"a\"}}", json!(null)),
+            "Template failed: can't compare number with string"
+        );
+        assert_eq!(
+            render_error("{{index . 5}}", json!([1])),
+            "Template failed: index 5 out of range"
+        );
+        assert_eq!(
+            render_error("{{not 1 2}}", json!(null)),
+            "Template failed: not takes 1 arguments, not 2"
+        );
+    }
+
+    #[test]
+    fn unsupported_syntax() {
+        assert_eq!(
+            parse_error("{{.items[1:]}}"),
+            "Invalid template: unexpected character '['"
+        );
+        assert_eq!(
+            parse_error("{{..}}"),
+            "Invalid template: `..` is not supported"
+        );
+        assert_eq!(
+            render_error(r#"{{printf "%x" 255}}"#, json!(null)),
+            "Template failed: printf verb %x is not supported"
+        );
+        assert_eq!(
+            parse_error("{{define \"t\"}}x{{end}}"),
+            "Invalid template: function \"define\" not defined"
+        );
+    }
+}
@@ -119,10 +119,8 @@ pub fn run_list_selection<'a, B: Backend>(
                         KeyCode::Left => list.unselect(),
                         KeyCode::Down => list.next(),
                         KeyCode::Up => list.previous(),
-                        KeyCode::Char('c') => {
-                            if key.modifiers == KeyModifiers::CONTROL {
-                                return Err(Error::msg("Nothing selected"));
-                            }
+                        KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => {
+                            return Err(Error::msg("Nothing selected"));
                         }
                         _ => {}
                     }
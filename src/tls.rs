@@ -0,0 +1,293 @@
+//! Custom TLS configuration for connecting to the gateway: private CAs,
+//! client certificates, disabled verification and public key pinning.
+
+use std::fs;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::config::Config;
+
+use anyhow::{Error, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ring::digest;
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, PrivateKey, RootCertStore, ServerName};
+
+const PIN_PREFIX: &str = "sha256/";
+
+/// Build a TLS configuration for the configured options. Returns `None`
+/// when no options are set and the default configuration can be used.
+pub fn client_config(config: &Config) -> Result<Option<ClientConfig>> {
+    let customized = config.ca_cert.is_some()
+        || config.client_cert.is_some()
+        || config.client_key.is_some()
+        || config.insecure_skip_verify
+        || !config.spki_pins.is_empty();
+    if !customized {
+        return Ok(None);
+    }
+
+    let mut roots = RootCertStore::empty();
+    match rustls_native_certs::load_native_certs() {
+        Ok(certs) => {
+            let certs: Vec<Vec<u8>> = certs.into_iter().map(|cert| cert.0).collect();
+            roots.add_parsable_certificates(&certs);
+        }
+        Err(err) => debug!("Failed to load native root certificates: {}", err),
+    }
+    if let Some(path) = &config.ca_cert {
+        let certs = read_certs(path)?;
+        let (_, invalid) = roots.add_parsable_certificates(&certs);
+        if invalid > 0 {
+            return Err(Error::msg(format!("Invalid CA certificate in {path:?}")));
+        }
+    }
+
+    if config.insecure_skip_verify {
+        warn!("TLS certificate verification is disabled");
+    }
+
+    let verifier = PinningVerifier {
+        inner: (!config.insecure_skip_verify).then(|| WebPkiVerifier::new(roots, None)),
+        pins: config
+            .spki_pins
+            .iter()
+            .map(|pin| parse_pin(pin))
+            .collect::<Result<_>>()?,
+    };
+
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(verifier));
+
+    let tls = match (&config.client_cert, &config.client_key) {
+        (Some(cert), Some(key)) => {
+            let certs = read_certs(cert)?.into_iter().map(Certificate).collect();
+            builder
+                .with_client_auth_cert(certs, read_key(key)?)
+                .map_err(|err| Error::msg(format!("Invalid client certificate: {err}")))?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        _ => {
+            return Err(Error::msg(
+                "client_cert and client_key must be set together",
+            ))
+        }
+    };
+
+    Ok(Some(tls))
+}
+
+/// Validate a pin, which is a base64 encoded SHA-256 hash optionally
+/// prefixed with `sha256/`, returning it without the prefix.
+pub fn parse_pin(pin: &str) -> Result<String> {
+    let pin = pin.trim().trim_start_matches(PIN_PREFIX);
+    match BASE64.decode(pin) {
+        Ok(hash) if hash.len() == digest::SHA256_OUTPUT_LEN => Ok(pin.to_string()),
+        _ => Err(Error::msg(format!(
+            "Invalid SPKI pin {pin}, expected a base64 encoded SHA-256 hash"
+        ))),
+    }
+}
+
+/// Return the pin for a DER encoded certificate: the base64 encoded
+/// SHA-256 hash of its SubjectPublicKeyInfo.
+pub fn spki_pin(cert: &[u8]) -> Option<String> {
+    let spki = subject_public_key_info(cert)?;
+    Some(BASE64.encode(digest::digest(&digest::SHA256, spki)))
+}
+
+/// Verifies server certificates with webpki, unless verification is
+/// disabled, and then checks the certificate chain against pinned keys.
+struct PinningVerifier {
+    inner: Option<WebPkiVerifier>,
+    pins: Vec<String>,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(inner) = &self.inner {
+            inner.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            )?;
+        }
+
+        if !self.pins.is_empty() {
+            let pinned = std::iter::once(end_entity)
+                .chain(intermediates)
+                .filter_map(|cert| spki_pin(&cert.0))
+                .any(|pin| self.pins.contains(&pin));
+            if !pinned {
+                return Err(rustls::Error::General(
+                    "server certificate does not match any pinned public key".to_string(),
+                ));
+            }
+        }
+
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+fn read_certs(path: &Path) -> Result<Vec<Vec<u8>>> {
+    let file = fs::File::open(path)
+        .map_err(|err| Error::msg(format!("Failed to read certificate {path:?}: {err}")))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))?;
+    if certs.is_empty() {
+        return Err(Error::msg(format!("No certificates found in {path:?}")));
+    }
+    Ok(certs)
+}
+
+fn read_key(path: &Path) -> Result<PrivateKey> {
+    let file = fs::File::open(path)
+        .map_err(|err| Error::msg(format!("Failed to read private key {path:?}: {err}")))?;
+
+    rustls_pemfile::read_all(&mut BufReader::new(file))?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| Error::msg(format!("No private key found in {path:?}")))
+}
+
+/// Extract the DER encoded SubjectPublicKeyInfo from a certificate
+fn subject_public_key_info(cert: &[u8]) -> Option<&[u8]> {
+    let (_, cert, _) = der_next(cert)?;
+    let (_, tbs, _) = der_next(cert)?;
+
+    // Skip the optional version, then the serial number, signature
+    // algorithm, issuer, validity and subject.
+    let mut rest = tbs;
+    if rest.first() == Some(&0xa0) {
+        rest = der_next(rest)?.2;
+    }
+    for _ in 0..5 {
+        rest = der_next(rest)?.2;
+    }
+
+    Some(der_next(rest)?.0)
+}
+
+/// Split the first DER element from `input`, returning the whole element,
+/// its contents and the remaining input.
+fn der_next(input: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    let first = *input.get(1)? as usize;
+    let (len, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let octets = first & 0x7f;
+        if octets == 0 || octets > 4 {
+            return None;
+        }
+        let len = input
+            .get(2..2 + octets)?
+            .iter()
+            .fold(0, |len, byte| (len << 8) | *byte as usize);
+        (len, 2 + octets)
+    };
+
+    let end = header.checked_add(len)?;
+    if end > input.len() {
+        return None;
+    }
+    Some((&input[..end], &input[header..end], &input[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn certificate() -> Vec<u8> {
+        let pem = include_bytes!("../tests/fixtures/gateway.pem");
+        rustls_pemfile::certs(&mut pem.as_slice())
+            .unwrap()
+            .remove(0)
+    }
+
+    #[test]
+    fn pin_of_a_certificate() {
+        // openssl x509 -pubkey -noout | openssl pkey -pubin -outform der
+        //   | openssl dgst -sha256 -binary | base64
+        assert_eq!(
+            spki_pin(&certificate()).unwrap(),
+            "qVv865ryZLEXcckhRFZMxbZra5oOx0mKXyFwJ7iLGfQ="
+        );
+    }
+
+    #[test]
+    fn truncated_certificates_have_no_pin() {
+        let cert = certificate();
+        for len in 0..cert.len() {
+            assert!(subject_public_key_info(&cert[..len]).is_none(), "{len}");
+        }
+    }
+
+    #[test]
+    fn malformed_certificates_have_no_pin() {
+        assert!(subject_public_key_info(b"not a certificate").is_none());
+
+        // A sequence holding an integer instead of the certificate fields
+        assert!(subject_public_key_info(&[0x30, 0x03, 0x02, 0x01, 0x00]).is_none());
+    }
+
+    #[test]
+    fn short_form_lengths() {
+        let (element, contents, rest) = der_next(&[0x04, 0x02, 0xaa, 0xbb, 0xff]).unwrap();
+        assert_eq!(element, [0x04, 0x02, 0xaa, 0xbb]);
+        assert_eq!(contents, [0xaa, 0xbb]);
+        assert_eq!(rest, [0xff]);
+
+        let (_, contents, rest) = der_next(&[0x05, 0x00]).unwrap();
+        assert!(contents.is_empty() && rest.is_empty());
+    }
+
+    #[test]
+    fn long_form_lengths() {
+        let mut input = vec![0x04, 0x81, 0x80];
+        input.extend([0xaa; 0x80]);
+        let (_, contents, rest) = der_next(&input).unwrap();
+        assert_eq!(contents.len(), 0x80);
+        assert!(rest.is_empty());
+
+        let mut input = vec![0x04, 0x82, 0x01, 0x00];
+        input.extend([0xaa; 0x101]);
+        let (element, contents, rest) = der_next(&input).unwrap();
+        assert_eq!(element.len(), 0x104);
+        assert_eq!(contents.len(), 0x100);
+        assert_eq!(rest, [0xaa]);
+    }
+
+    #[test]
+    fn invalid_lengths() {
+        // Missing length
+        assert!(der_next(&[]).is_none());
+        assert!(der_next(&[0x04]).is_none());
+        // Contents shorter than the length
+        assert!(der_next(&[0x04, 0x03, 0xaa]).is_none());
+        // Indefinite length, which isn't allowed in DER
+        assert!(der_next(&[0x04, 0x80, 0xaa, 0x00, 0x00]).is_none());
+        // Long form with missing or too many length octets
+        assert!(der_next(&[0x04, 0x82, 0x01]).is_none());
+        assert!(der_next(&[0x04, 0x85, 0, 0, 0, 0, 1, 0xaa]).is_none());
+        // A length far beyond the end of the input
+        assert!(der_next(&[0x04, 0x84, 0xff, 0xff, 0xff, 0xff]).is_none());
+    }
+}
@@ -0,0 +1,351 @@
+//! Minimal line-based editing of YAML block mappings.
+//!
+//! Only the lines belonging to the edited key are touched, so unrelated
+//! keys, formatting and comments in the document are kept as-is. Values
+//! written in flow style, eg `{a: 1}`, can be replaced but not edited into.
+
+use anyhow::{Error, Result};
+
+enum Location {
+    /// The key exists at the given line
+    Found(usize),
+
+    /// The key is missing. `depth` path segments already exist, the last of
+    /// them at line `parent` when it has an inline value. New lines should
+    /// be inserted at `insert_at` using `indent` spaces.
+    Missing {
+        parent: Option<usize>,
+        insert_at: usize,
+        indent: usize,
+        depth: usize,
+    },
+}
+
+/// Set the value at `path`, creating any missing parent mappings. `value`
+/// must already be formatted as a YAML scalar. Fails when a parent has a
+/// value that would be lost, such as a flow mapping or a scalar.
+pub fn set(doc: &str, path: &[&str], value: &str) -> Result<String> {
+    let mut lines: Vec<String> = doc.lines().map(String::from).collect();
+
+    match locate(&lines, path) {
+        Location::Found(i) => {
+            let indent = indent_of(&lines[i]);
+            let comment = trailing_comment(&lines[i]).unwrap_or_default();
+            let end = content_end(&lines, i);
+            lines.drain(i + 1..end);
+            lines[i] = format!(
+                "{}{}: {}{}",
+                " ".repeat(indent),
+                path[path.len() - 1],
+                value,
+                comment
+            );
+        }
+        Location::Missing {
+            parent,
+            insert_at,
+            indent,
+            depth,
+        } => {
+            // An empty parent written inline, eg `contexts: {}`, is turned
+            // into a block mapping so children can be added.
+            if let Some(parent) = parent {
+                if !is_empty_value(&lines[parent]) {
+                    return Err(not_editable(&path[..depth]));
+                }
+                let parent_indent = indent_of(&lines[parent]);
+                lines[parent] = format!("{}{}:", " ".repeat(parent_indent), path[depth - 1]);
+            }
+
+            let remaining = &path[depth..];
+            let new_lines = remaining.iter().enumerate().map(|(n, key)| {
+                let padding = " ".repeat(indent + 2 * n);
+                if n == remaining.len() - 1 {
+                    format!("{padding}{key}: {value}")
+                } else {
+                    format!("{padding}{key}:")
+                }
+            });
+            lines.splice(insert_at..insert_at, new_lines);
+        }
+    }
+
+    Ok(to_document(lines))
+}
+
+/// Remove the key at `path`, along with any nested values. Returns `None`
+/// when the key does not exist.
+pub fn unset(doc: &str, path: &[&str]) -> Result<Option<String>> {
+    let mut lines: Vec<String> = doc.lines().map(String::from).collect();
+
+    match locate(&lines, path) {
+        Location::Found(i) => {
+            let end = content_end(&lines, i);
+            lines.drain(i..end);
+            Ok(Some(to_document(lines)))
+        }
+        Location::Missing {
+            parent: Some(parent),
+            depth,
+            ..
+        } if !is_empty_value(&lines[parent]) => Err(not_editable(&path[..depth])),
+        Location::Missing { .. } => Ok(None),
+    }
+}
+
+fn not_editable(path: &[&str]) -> Error {
+    Error::msg(format!(
+        "Can't edit inside {}, as it is not written as a block mapping. Edit the file by hand instead",
+        path.join(".")
+    ))
+}
+
+/// Format a string as a YAML scalar, quoting it when a plain scalar would be
+/// parsed as a different value or type.
+pub fn scalar(value: &str) -> String {
+    let lower = value.to_lowercase();
+    let ambiguous = matches!(
+        lower.as_str(),
+        "" | "~" | "null" | "true" | "false" | "yes" | "no" | "on" | "off"
+    ) || value.parse::<f64>().is_ok();
+
+    let plain = value.chars().all(|c| {
+        c.is_ascii_alphanumeric()
+            || matches!(c, '_' | '-' | '.' | '/' | ':' | '@' | '%' | '+' | '=')
+    }) && !value.starts_with(['-', ':', '%', '@'])
+        && !value.ends_with(':');
+
+    if plain && !ambiguous {
+        value.to_string()
+    } else {
+        // JSON strings are valid YAML double-quoted scalars
+        serde_json::Value::String(value.to_string()).to_string()
+    }
+}
+
+fn locate(lines: &[String], path: &[&str]) -> Location {
+    let mut start = 0;
+    let mut end = lines.len();
+    let mut parent: Option<usize> = None;
+
+    for (depth, segment) in path.iter().enumerate() {
+        let child_indent = lines[start..end]
+            .iter()
+            .find(|l| is_content(l))
+            .map(|l| indent_of(l));
+
+        let found = child_indent.and_then(|child_indent| {
+            (start..end).find(|&i| {
+                is_content(&lines[i])
+                    && indent_of(&lines[i]) == child_indent
+                    && key_of(&lines[i]) == Some(segment)
+            })
+        });
+
+        match found {
+            Some(i) if depth == path.len() - 1 => return Location::Found(i),
+            Some(i) => {
+                start = i + 1;
+                end = block_end(lines, i);
+                parent = Some(i);
+            }
+            None => {
+                let indent = child_indent
+                    .or_else(|| parent.map(|p| indent_of(&lines[p]) + 2))
+                    .unwrap_or(0);
+                let insert_at = (start..end)
+                    .rev()
+                    .find(|&i| is_content(&lines[i]))
+                    .map(|i| i + 1)
+                    .unwrap_or(start);
+                let parent = parent.filter(|&p| has_inline_value(&lines[p]));
+
+                return Location::Missing {
+                    parent,
+                    insert_at,
+                    indent,
+                    depth,
+                };
+            }
+        }
+    }
+
+    unreachable!("an empty path cannot be located")
+}
+
+/// Index of the first line after the block started at `start`, including
+/// nested lines but excluding trailing blank lines and comments.
+fn content_end(lines: &[String], start: usize) -> usize {
+    let end = block_end(lines, start);
+    (start + 1..end)
+        .rev()
+        .find(|&i| is_content(&lines[i]))
+        .map(|i| i + 1)
+        .unwrap_or(start + 1)
+}
+
+/// Index of the next line that is not nested under the line at `start`.
+fn block_end(lines: &[String], start: usize) -> usize {
+    let indent = indent_of(&lines[start]);
+    (start + 1..lines.len())
+        .find(|&i| is_content(&lines[i]) && indent_of(&lines[i]) <= indent)
+        .unwrap_or(lines.len())
+}
+
+fn is_content(line: &str) -> bool {
+    let trimmed = line.trim();
+    !trimmed.is_empty() && !trimmed.starts_with('#')
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Split a mapping line into its key and the remainder after the colon
+fn split_key(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('-') {
+        return None;
+    }
+
+    let bytes = trimmed.as_bytes();
+    let colon = (0..bytes.len())
+        .find(|&i| bytes[i] == b':' && (i + 1 == bytes.len() || bytes[i + 1] == b' '))?;
+    let key = trimmed[..colon]
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'');
+    Some((key, &trimmed[colon + 1..]))
+}
+
+fn key_of(line: &str) -> Option<&str> {
+    split_key(line).map(|(key, _)| key)
+}
+
+fn has_inline_value(line: &str) -> bool {
+    split_key(line)
+        .map(|(_, rest)| is_content(rest))
+        .unwrap_or(false)
+}
+
+/// Whether an inline value can be replaced by a mapping without losing
+/// anything, eg `{}` or `~`
+fn is_empty_value(line: &str) -> bool {
+    let value = match split_key(line) {
+        Some((_, rest)) => rest,
+        None => return false,
+    };
+    let value = match value.find(" #") {
+        Some(comment) => &value[..comment],
+        None => value,
+    };
+    matches!(value.trim(), "" | "{}" | "~" | "null")
+}
+
+/// Comment following an inline value, preceded by its original spacing
+fn trailing_comment(line: &str) -> Option<String> {
+    let (_, rest) = split_key(line)?;
+    let value = rest.trim_start();
+    if value.starts_with(['"', '\'']) {
+        return None;
+    }
+
+    let start = rest.find(" #")?;
+    let spacing = rest[..start].len() - rest[..start].trim_end().len();
+    Some(format!("{}{}", " ".repeat(spacing), &rest[start..]))
+}
+
+fn to_document(lines: Vec<String>) -> String {
+    if lines.is_empty() {
+        String::new()
+    } else {
+        lines.join("\n") + "\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_keeps_comments_and_other_keys() {
+        let doc = "# jwctl\nurl: http://old # the gateway\n\nretries: 3\n";
+        let doc = set(doc, &["url"], "http://new").unwrap();
+        assert_eq!(
+            doc,
+            "# jwctl\nurl: http://new # the gateway\n\nretries: 3\n"
+        );
+    }
+
+    #[test]
+    fn set_creates_nested_mappings() {
+        let doc = set("url: http://a\n", &["contexts", "dev", "url"], "http://b").unwrap();
+        assert_eq!(doc, "url: http://a\ncontexts:\n  dev:\n    url: http://b\n");
+
+        let doc = set(&doc, &["contexts", "prod", "token"], "secret").unwrap();
+        assert_eq!(
+            doc,
+            "url: http://a\ncontexts:\n  dev:\n    url: http://b\n  prod:\n    token: secret\n"
+        );
+    }
+
+    #[test]
+    fn set_replaces_nested_values() {
+        let doc = "headers:\n  X-One: a\n  X-Two: b\nretries: 3\n";
+        assert_eq!(
+            set(doc, &["headers"], "x").unwrap(),
+            "headers: x\nretries: 3\n"
+        );
+    }
+
+    #[test]
+    fn set_expands_empty_flow_mappings() {
+        let doc = set(
+            "contexts: {} # none yet\n",
+            &["contexts", "dev", "url"],
+            "x",
+        )
+        .unwrap();
+        assert_eq!(doc, "contexts:\n  dev:\n    url: x\n");
+    }
+
+    #[test]
+    fn set_refuses_to_edit_inside_flow_mappings() {
+        let doc = "contexts: {prod: {url: http://prod}}\n";
+        let err = set(doc, &["contexts", "staging", "url"], "x").unwrap_err();
+        assert!(err.to_string().contains("inside contexts"), "{err}");
+
+        let err = set("url: http://a\n", &["url", "path"], "x").unwrap_err();
+        assert!(err.to_string().contains("inside url"), "{err}");
+
+        // Replacing the flow mapping as a whole is fine
+        assert_eq!(set(doc, &["contexts"], "~").unwrap(), "contexts: ~\n");
+    }
+
+    #[test]
+    fn unset_removes_nested_values() {
+        let doc = "contexts:\n  dev:\n    url: http://b\n  # prod\n  prod:\n    url: http://c\nretries: 3\n";
+        let doc = unset(doc, &["contexts", "dev"]).unwrap().unwrap();
+        assert_eq!(
+            doc,
+            "contexts:\n  # prod\n  prod:\n    url: http://c\nretries: 3\n"
+        );
+
+        assert!(unset(&doc, &["contexts", "dev"]).unwrap().is_none());
+        assert!(unset(&doc, &["timeout"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn unset_refuses_to_edit_inside_flow_mappings() {
+        let doc = "contexts: {prod: {url: http://prod}}\n";
+        assert!(unset(doc, &["contexts", "prod"]).is_err());
+    }
+
+    #[test]
+    fn scalars_are_quoted_when_ambiguous() {
+        assert_eq!(scalar("http://localhost:4004"), "http://localhost:4004");
+        assert_eq!(scalar("true"), "\"true\"");
+        assert_eq!(scalar("42"), "\"42\"");
+        assert_eq!(scalar(""), "\"\"");
+        assert_eq!(scalar("a b # c"), "\"a b # c\"");
+    }
+}
@@ -0,0 +1,485 @@
+//! Test harness running the jwctl binary against an in-process stub of the
+//! JumpWire API.
+
+#![allow(dead_code)]
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Self-signed certificate served by `MockGateway::start_tls`, and the pin
+/// of its public key
+pub const TLS_CERT: &str = "tests/fixtures/gateway.pem";
+pub const TLS_KEY: &str = "tests/fixtures/gateway-key.pem";
+pub const TLS_PIN: &str = "sha256/qVv865ryZLEXcckhRFZMxbZra5oOx0mKXyFwJ7iLGfQ=";
+
+pub const MANIFEST_ID: &str = "0779b97a-c04a-48f9-9483-22e8b0487de4";
+pub const SCHEMA_ID: &str = "5e0c1f3a-8d3b-4c8e-9f0e-2b1a7c6d4e21";
+pub const CLIENT_ID: &str = "9d2f6a4e-3c1b-4f7a-8e5d-1a2b3c4d5e6f";
+
+/// A request received by the stub gateway
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl Request {
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Response {
+    status: u16,
+    content_type: &'static str,
+    headers: Vec<(String, String)>,
+    body: String,
+    delay: Duration,
+}
+
+impl Response {
+    pub fn json(status: u16, body: Value) -> Response {
+        Response {
+            status,
+            content_type: "application/json",
+            headers: vec![],
+            body: body.to_string(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn text(status: u16, body: &str) -> Response {
+        Response {
+            status,
+            content_type: "text/plain",
+            headers: vec![],
+            body: body.to_string(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Response {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Wait before sending the response
+    pub fn delay(mut self, delay: Duration) -> Response {
+        self.delay = delay;
+        self
+    }
+}
+
+struct Route {
+    method: String,
+    path: String,
+    response: Response,
+}
+
+/// Stub of the JumpWire API listening on a random local port. Routes added
+/// later take precedence, so tests can override the default responses.
+pub struct MockGateway {
+    routes: Arc<Mutex<Vec<Route>>>,
+    requests: Arc<Mutex<Vec<Request>>>,
+    pub url: String,
+}
+
+impl MockGateway {
+    /// Start a gateway responding to every endpoint jwctl uses
+    pub fn start() -> MockGateway {
+        MockGateway::listen("http", Some)
+    }
+
+    /// Start a gateway served over TLS with the `TLS_CERT` certificate
+    pub fn start_tls() -> MockGateway {
+        let certs = rustls_pemfile::certs(&mut BufReader::new(fs::File::open(TLS_CERT).unwrap()))
+            .unwrap()
+            .into_iter()
+            .map(rustls::Certificate)
+            .collect();
+        let key = rustls_pemfile::pkcs8_private_keys(&mut BufReader::new(
+            fs::File::open(TLS_KEY).unwrap(),
+        ))
+        .unwrap()
+        .remove(0);
+        let config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certs, rustls::PrivateKey(key))
+            .unwrap();
+        let config = Arc::new(config);
+
+        MockGateway::listen("https", move |stream| {
+            let conn = rustls::ServerConnection::new(Arc::clone(&config)).ok()?;
+            Some(rustls::StreamOwned::new(conn, stream))
+        })
+    }
+
+    fn listen<S, F>(scheme: &str, accept: F) -> MockGateway
+    where
+        S: Read + Write,
+        F: Fn(TcpStream) -> Option<S> + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("{scheme}://{}", listener.local_addr().unwrap());
+        let gateway = MockGateway {
+            routes: Arc::new(Mutex::new(vec![])),
+            requests: Arc::new(Mutex::new(vec![])),
+            url,
+        };
+
+        let routes = Arc::clone(&gateway.routes);
+        let requests = Arc::clone(&gateway.requests);
+        let accept = Arc::new(accept);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let routes = Arc::clone(&routes);
+                let requests = Arc::clone(&requests);
+                let accept = Arc::clone(&accept);
+                thread::spawn(move || {
+                    if let Some(stream) = accept(stream) {
+                        handle(stream, &routes, &requests);
+                    }
+                });
+            }
+        });
+
+        gateway.add_default_routes();
+        gateway
+    }
+
+    pub fn route(&self, method: &str, path: &str, response: Response) -> &MockGateway {
+        self.routes.lock().unwrap().push(Route {
+            method: method.to_string(),
+            path: path.to_string(),
+            response,
+        });
+        self
+    }
+
+    /// Requests received so far
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// The last request received for a path
+    pub fn last_request(&self, method: &str, path: &str) -> Request {
+        self.requests()
+            .into_iter()
+            .rev()
+            .find(|r| r.method == method && r.path == path)
+            .unwrap_or_else(|| panic!("no {method} {path} request received"))
+    }
+
+    fn add_default_routes(&self) {
+        let manifest = json!({
+            "id": MANIFEST_ID,
+            "name": "customers",
+            "root_type": "postgresql",
+            "configuration": {"type": "postgresql", "hostname": "db.internal", "database": "customers"},
+        });
+        let schema = json!({
+            "id": SCHEMA_ID,
+            "manifest_id": MANIFEST_ID,
+            "name": "users",
+            "fields": {"email": "pii"},
+        });
+        let manifest_path = format!("/api/v1/manifests/{MANIFEST_ID}");
+        let schemas_path = format!("{manifest_path}/proxy-schemas");
+        let schema_path = format!("{schemas_path}/{SCHEMA_ID}");
+
+        self.route("GET", "/ping", Response::text(200, "pong"))
+            .route(
+                "GET",
+                "/api/v1/status",
+                Response::json(200, json!({"status": "ok", "version": "4.1.0"})),
+            )
+            .route(
+                "GET",
+                "/api/v1/token",
+                Response::json(200, json!({"permissions": {"get": ["status", "token"]}})),
+            )
+            .route(
+                "POST",
+                "/api/v1/token",
+                Response::json(200, json!({"token": "generated-token"})),
+            )
+            .route("GET", "/sso", Response::json(200, json!(["okta"])))
+            .route(
+                "GET",
+                "/sso/whoami",
+                Response::json(200, json!({"email": "ada@example.com"})),
+            )
+            .route(
+                "GET",
+                "/api/v1/manifests/postgresql",
+                Response::json(200, json!({MANIFEST_ID: "customers"})),
+            )
+            .route(
+                "GET",
+                "/api/v1/auth/db-token",
+                Response::json(200, json!({})),
+            )
+            .route(
+                "PUT",
+                "/api/v1/auth/db-token",
+                Response::json(200, json!({"status": "ok"})),
+            )
+            .route(
+                "GET",
+                &format!("/api/v1/client/{CLIENT_ID}"),
+                Response::json(
+                    200,
+                    json!({"id": CLIENT_ID, "name": "psql", "manifest_id": MANIFEST_ID}),
+                ),
+            )
+            .route(
+                "PUT",
+                &format!("/api/v1/client/{CLIENT_ID}/token"),
+                Response::json(
+                    200,
+                    json!({
+                        "id": CLIENT_ID,
+                        "token": "client-token",
+                        "manifest_id": MANIFEST_ID,
+                        "protocol": "postgresql",
+                        "port": 5432,
+                        "database": "customers",
+                    }),
+                ),
+            )
+            .route(
+                "GET",
+                "/api/v1/manifests",
+                Response::json(200, json!([manifest])),
+            )
+            .route("GET", &manifest_path, Response::json(200, manifest.clone()))
+            .route(
+                "DELETE",
+                &manifest_path,
+                Response::json(200, json!({"id": MANIFEST_ID})),
+            )
+            .route("GET", &schemas_path, Response::json(200, json!([schema])))
+            .route("GET", &schema_path, Response::json(200, schema.clone()))
+            .route(
+                "DELETE",
+                &schema_path,
+                Response::json(200, json!({"id": SCHEMA_ID})),
+            );
+    }
+}
+
+fn handle(stream: impl Read + Write, routes: &Mutex<Vec<Route>>, requests: &Mutex<Vec<Request>>) {
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+        return;
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).is_err() {
+            return;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length = headers
+        .get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+
+    let path = target.split('?').next().unwrap_or_default().to_string();
+    requests.lock().unwrap().push(Request {
+        method: method.clone(),
+        path: target,
+        headers,
+        body: String::from_utf8_lossy(&body).to_string(),
+    });
+
+    let response = routes
+        .lock()
+        .unwrap()
+        .iter()
+        .rev()
+        .find(|route| route.method == method && route.path == path)
+        .map(|route| route.response.clone())
+        .unwrap_or_else(|| Response::json(404, json!({"error": "not found"})));
+
+    thread::sleep(response.delay);
+    let headers: String = response
+        .headers
+        .iter()
+        .map(|(name, value)| format!("{name}: {value}\r\n"))
+        .collect();
+    let stream = reader.get_mut();
+    write!(
+        stream,
+        "HTTP/1.1 {} Stub\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        headers,
+        response.body
+    )
+    .unwrap();
+    stream.flush().unwrap();
+}
+
+/// A directory removed when dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> TempDir {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = env::temp_dir().join(format!(
+            "jwctl-test-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Runs jwctl with its own configuration directory, isolated from the user's
+/// configuration and environment
+pub struct Jwctl {
+    dir: TempDir,
+    url: Option<String>,
+}
+
+impl Jwctl {
+    pub fn new() -> Jwctl {
+        Jwctl {
+            dir: TempDir::new(),
+            url: None,
+        }
+    }
+
+    /// Use the gateway as the configured URL
+    pub fn gateway(gateway: &MockGateway) -> Jwctl {
+        Jwctl {
+            url: Some(gateway.url.clone()),
+            ..Jwctl::new()
+        }
+    }
+
+    /// Use a gateway URL, eg one with a path prefix
+    pub fn url(url: &str) -> Jwctl {
+        Jwctl {
+            url: Some(url.to_string()),
+            ..Jwctl::new()
+        }
+    }
+
+    /// The directory jwctl is run in
+    pub fn work_dir(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn config_dir(&self) -> PathBuf {
+        self.dir.path().join("config")
+    }
+
+    pub fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_jwctl"));
+        command
+            .args(args)
+            .current_dir(self.dir.path())
+            .env("JW_CONFIG_DIR", self.config_dir())
+            .env("JW_RETRIES", "0")
+            .env_remove("JW_TOKEN")
+            .env_remove("JW_CONTEXT")
+            .env_remove("JW_CREDENTIAL_STORE")
+            .stdin(Stdio::null());
+        match &self.url {
+            Some(url) => command.env("JW_URL", url),
+            None => command.env_remove("JW_URL"),
+        };
+        command
+    }
+
+    pub fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    /// Run a command that is expected to succeed, returning its stdout
+    pub fn success(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(
+            output.status.success(),
+            "jwctl {} failed with {}\nstdout: {}\nstderr: {}",
+            args.join(" "),
+            output.status,
+            stdout(&output),
+            stderr(&output)
+        );
+        stdout(&output)
+    }
+
+    /// Run a command that is expected to fail with an exit code, returning
+    /// its stderr
+    pub fn failure(&self, args: &[&str], code: i32) -> String {
+        let output = self.run(args);
+        assert_eq!(
+            output.status.code(),
+            Some(code),
+            "jwctl {}\nstdout: {}\nstderr: {}",
+            args.join(" "),
+            stdout(&output),
+            stderr(&output)
+        );
+        stderr(&output)
+    }
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+pub fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
@@ -0,0 +1,270 @@
+//! Error responses and HTTP failures, and the exit code for each.
+
+mod common;
+
+use common::{Jwctl, MockGateway, Response, MANIFEST_ID};
+use serde_json::json;
+use std::time::Duration;
+
+fn manifest_path() -> String {
+    format!("/api/v1/manifests/{MANIFEST_ID}")
+}
+
+#[test]
+fn unauthorized() {
+    let gateway = MockGateway::start();
+    gateway.route(
+        "GET",
+        "/api/v1/status",
+        Response::json(401, json!({"error": "token expired"})),
+    );
+    let stderr = Jwctl::gateway(&gateway).failure(&["status"], 4);
+
+    assert!(
+        stderr.contains("not authenticated, check the token or log in again: token expired"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn forbidden() {
+    let gateway = MockGateway::start();
+    gateway.route("GET", "/api/v1/manifests", Response::json(403, json!({})));
+    let stderr = Jwctl::gateway(&gateway).failure(&["manifest", "list"], 4);
+
+    assert!(stderr.contains("token lacks get:manifests"), "{stderr}");
+}
+
+#[test]
+fn not_found() {
+    let gateway = MockGateway::start();
+    let stderr = Jwctl::gateway(&gateway).failure(&["manifest", "get", "missing"], 5);
+
+    assert!(
+        stderr.contains("manifest missing not found: not found"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("GET /api/v1/manifests/missing failed (request ID"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn conflict() {
+    let gateway = MockGateway::start();
+    gateway.route(
+        "DELETE",
+        &manifest_path(),
+        Response::json(409, json!({"error": "manifest has schemas"})),
+    );
+    let stderr = Jwctl::gateway(&gateway).failure(&["manifest", "delete", MANIFEST_ID], 6);
+
+    assert!(stderr.contains("manifest has schemas"), "{stderr}");
+}
+
+#[test]
+fn server_error() {
+    let gateway = MockGateway::start();
+    gateway.route("GET", "/api/v1/status", Response::text(500, "oops"));
+    let stderr = Jwctl::gateway(&gateway).failure(&["status"], 7);
+
+    assert!(
+        stderr.contains("gateway failed with 500 Internal Server Error"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn other_status() {
+    let gateway = MockGateway::start();
+    gateway.route(
+        "POST",
+        "/api/v1/token",
+        Response::json(422, json!({"error": {"permissions": "unknown action"}})),
+    );
+    let stderr = Jwctl::gateway(&gateway).failure(&["token", "generate", "get:nothing"], 1);
+
+    assert!(
+        stderr.contains("request failed with 422 Unprocessable Entity"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("unknown action"), "{stderr}");
+}
+
+#[test]
+fn error_body() {
+    let gateway = MockGateway::start();
+    gateway.route(
+        "GET",
+        "/sso/whoami",
+        Response::json(200, json!({"error": "not logged in"})),
+    );
+    let stderr = Jwctl::gateway(&gateway).failure(&["auth", "whoami"], 1);
+
+    assert!(stderr.contains("not logged in"), "{stderr}");
+}
+
+#[test]
+fn response_is_not_json() {
+    let gateway = MockGateway::start();
+    gateway.route("GET", "/api/v1/manifests", Response::text(200, "<html>"));
+    let stderr = Jwctl::gateway(&gateway).failure(&["manifest", "list"], 7);
+
+    assert!(
+        stderr.contains("expected a JSON response but got text/plain"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn unavailable_gateway_is_retried() {
+    let gateway = MockGateway::start();
+    gateway.route("GET", "/api/v1/status", Response::text(503, "restarting"));
+    let output = Jwctl::gateway(&gateway).run(&["--retries", "1", "status"]);
+
+    assert_eq!(output.status.code(), Some(7));
+    let stderr = common::stderr(&output);
+    assert!(stderr.contains("retrying GET /api/v1/status"), "{stderr}");
+    let attempts = gateway
+        .requests()
+        .iter()
+        .filter(|r| r.path == "/api/v1/status")
+        .count();
+    assert_eq!(attempts, 2);
+}
+
+#[test]
+fn changes_are_not_retried() {
+    let gateway = MockGateway::start();
+    gateway.route("POST", "/api/v1/token", Response::text(503, "restarting"));
+    Jwctl::gateway(&gateway).failure(&["--retries", "1", "token", "generate", "get:status"], 7);
+
+    let attempts = gateway
+        .requests()
+        .iter()
+        .filter(|r| r.path == "/api/v1/token")
+        .count();
+    assert_eq!(attempts, 1);
+}
+
+#[test]
+fn unreachable_gateway() {
+    let stderr = Jwctl::new().failure(&["--url", "http://127.0.0.1:1", "status"], 8);
+
+    assert!(stderr.contains("Connection refused"), "{stderr}");
+}
+
+#[test]
+fn pinned_key_mismatch() {
+    let gateway = MockGateway::start_tls();
+    let jwctl = Jwctl::gateway(&gateway);
+    let other_pin = "sha256/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=";
+
+    let stderr = jwctl.failure(
+        &["--insecure-skip-verify", "--spki-pins", other_pin, "status"],
+        8,
+    );
+    assert!(stderr.contains("pinned public key"), "{stderr}");
+    assert!(gateway.requests().is_empty());
+
+    let pins = format!("{other_pin},{}", common::TLS_PIN);
+    jwctl.success(&["--insecure-skip-verify", "--spki-pins", &pins, "status"]);
+    gateway.last_request("GET", "/api/v1/status");
+}
+
+#[test]
+fn slow_gateway_times_out() {
+    let gateway = MockGateway::start();
+    gateway.route(
+        "GET",
+        "/api/v1/status",
+        Response::json(200, json!({"status": "ok"})).delay(Duration::from_secs(2)),
+    );
+    let jwctl = Jwctl::gateway(&gateway);
+
+    let output = jwctl
+        .command(&["status"])
+        .env("JW_TIMEOUT", "1")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(8));
+    let stderr = common::stderr(&output);
+    assert!(stderr.contains("timed out"), "{stderr}");
+
+    // The connect timeout only limits connecting, not the whole request
+    let output = jwctl
+        .command(&["status"])
+        .env("JW_CONNECT_TIMEOUT", "1")
+        .env("JW_TIMEOUT", "0")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", common::stderr(&output));
+}
+
+#[test]
+fn missing_url() {
+    let stderr = Jwctl::new().failure(&["status"], 3);
+
+    assert!(stderr.contains("Invalid configuration"), "{stderr}");
+}
+
+#[test]
+fn invalid_arguments() {
+    Jwctl::new().failure(&["manifest", "frobnicate"], 2);
+}
+
+#[test]
+fn dry_run_does_not_send_changes() {
+    let gateway = MockGateway::start();
+    let output = Jwctl::gateway(&gateway).run(&["--dry-run", "manifest", "delete", MANIFEST_ID]);
+
+    assert!(output.status.success());
+    let stderr = common::stderr(&output);
+    assert!(
+        stderr.contains(&format!("DELETE {}{}", gateway.url, manifest_path())),
+        "{stderr}"
+    );
+    assert!(gateway.requests().is_empty());
+}
+
+#[test]
+fn dry_run_sends_reads() {
+    let gateway = MockGateway::start();
+    let output = Jwctl::gateway(&gateway).run(&["--dry-run", "manifest", "list"]);
+
+    assert!(output.status.success());
+    assert!(common::stdout(&output).contains(MANIFEST_ID));
+    assert!(
+        common::stderr(&output).is_empty(),
+        "{}",
+        common::stderr(&output)
+    );
+    assert_eq!(gateway.requests().len(), 1);
+}
+
+#[test]
+fn dry_run_redacts_credentials_in_the_body() {
+    let gateway = MockGateway::start();
+    let args = [
+        "--dry-run",
+        "api",
+        "POST",
+        "/api/v1/token",
+        "-f",
+        "name=ci",
+        "-f",
+        "password=hunter2",
+    ];
+    let output = Jwctl::gateway(&gateway).run(&args);
+
+    assert!(output.status.success());
+    let stderr = common::stderr(&output);
+    assert!(stderr.contains("\"name\": \"ci\""), "{stderr}");
+    assert!(stderr.contains("\"password\": \"<redacted>\""), "{stderr}");
+    assert!(!stderr.contains("hunter2"), "{stderr}");
+
+    let output = Jwctl::gateway(&gateway).run(&[&["--raw-body"], &args[..]].concat());
+    assert!(common::stderr(&output).contains("hunter2"));
+    assert!(gateway.requests().is_empty());
+}
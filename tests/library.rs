@@ -0,0 +1,54 @@
+//! The jwctl library used directly, for commands whose input is prompted
+//! for in a terminal.
+
+mod common;
+
+use common::{Jwctl, MockGateway, MANIFEST_ID};
+use jwctl::config::Config;
+use jwctl::errors::DryRun;
+use jwctl::http::{self, ApiClient};
+use jwctl::manifests;
+use jwctl::schemas::{self, ProxySchema};
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+
+static PREVIEWS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// `schema create` lists the manifests to prompt for one, then creates the
+/// schema. With `--dry-run` the list is sent and the creation is previewed.
+#[test]
+fn schema_create_dry_run_previews_the_post() {
+    let gateway = MockGateway::start();
+    let jwctl = Jwctl::gateway(&gateway);
+    env::set_var("JW_CONFIG_DIR", jwctl.config_dir());
+    http::set_preview_printer(|preview| PREVIEWS.lock().unwrap().push(preview.to_string()));
+
+    let mut config = Config::new(gateway.url.parse().unwrap());
+    config.dry_run = true;
+    let api = ApiClient::new(&config).unwrap();
+
+    let manifests = manifests::list(&api).unwrap();
+    assert_eq!(manifests[0].id, MANIFEST_ID);
+
+    let schema = ProxySchema {
+        manifest_id: MANIFEST_ID.to_string(),
+        name: "users".to_string(),
+        fields: HashMap::from([("email".to_string(), "pii".to_string())]),
+    };
+    let err = schemas::create(&api, &schema).unwrap_err();
+    assert!(err.is::<DryRun>(), "{err:?}");
+
+    let previews = PREVIEWS.lock().unwrap();
+    assert_eq!(previews.len(), 1, "{previews:?}");
+    let preview = &previews[0];
+    let url = format!(
+        "{}/api/v1/manifests/{MANIFEST_ID}/proxy-schemas",
+        gateway.url
+    );
+    assert!(preview.starts_with(&format!("POST {url}\n")), "{preview}");
+    assert!(preview.contains("\"email\": \"pii\""), "{preview}");
+
+    let paths: Vec<String> = gateway.requests().into_iter().map(|r| r.path).collect();
+    assert_eq!(paths, ["/api/v1/manifests"]);
+}
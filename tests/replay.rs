@@ -0,0 +1,105 @@
+//! Commands run against responses replayed from cassettes in
+//! `tests/fixtures`, without a gateway.
+
+mod common;
+
+use common::{fixture, Jwctl, MockGateway, Response, MANIFEST_ID};
+use serde_json::json;
+use std::fs;
+
+/// Run jwctl replaying the manifests cassette
+fn replay(jwctl: &Jwctl, args: &[&str]) -> std::process::Output {
+    let cassette = fixture("manifests.yaml");
+    let mut command = jwctl.command(&["--replay", cassette.to_str().unwrap()]);
+    command
+        .args(args)
+        .env("JW_URL", "http://localhost:4443")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn manifest_list() {
+    let output = replay(&Jwctl::new(), &["manifest", "list"]);
+
+    assert!(output.status.success(), "{}", common::stderr(&output));
+    let stdout = common::stdout(&output);
+    assert!(stdout.contains(MANIFEST_ID), "{stdout}");
+    assert!(stdout.contains("\"name\": \"customers\""), "{stdout}");
+}
+
+#[test]
+fn schema_list() {
+    let output = replay(&Jwctl::new(), &["schema", "list", MANIFEST_ID]);
+
+    assert!(output.status.success(), "{}", common::stderr(&output));
+    let stdout = common::stdout(&output);
+    assert!(stdout.contains("\"name\": \"users\""), "{stdout}");
+    assert!(stdout.contains("\"email\": \"pii\""), "{stdout}");
+}
+
+#[test]
+fn select_manifest_lists_manifests_before_prompting() {
+    let output = replay(&Jwctl::new(), &["schema", "create"]);
+
+    // The manifests are replayed, then the prompt fails without a terminal
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = common::stderr(&output);
+    assert!(stderr.contains("not a TTY"), "{stderr}");
+}
+
+#[test]
+fn unrecorded_request_fails() {
+    let output = replay(&Jwctl::new(), &["status"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = common::stderr(&output);
+    assert!(
+        stderr.contains("No recorded response to GET http://localhost:4443/api/v1/status"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("request ID"), "{stderr}");
+}
+
+#[test]
+fn record_then_replay() {
+    let gateway = MockGateway::start();
+    let jwctl = Jwctl::gateway(&gateway);
+    let cassette = jwctl.config_dir().with_file_name("session.yaml");
+    let cassette = cassette.to_str().unwrap();
+
+    jwctl.success(&["--token", "secret-token", "--record", cassette, "status"]);
+
+    let recorded = fs::read_to_string(cassette).unwrap();
+    assert!(recorded.contains("/api/v1/status"), "{recorded}");
+    assert!(recorded.contains("Bearer <redacted>"), "{recorded}");
+    assert!(!recorded.contains("secret-token"), "{recorded}");
+
+    let requests = gateway.requests().len();
+    let stdout = jwctl.success(&["--replay", cassette, "status"]);
+    assert!(stdout.contains("\"version\": \"4.1.0\""), "{stdout}");
+    assert_eq!(gateway.requests().len(), requests, "replay sent a request");
+}
+
+#[test]
+fn replay_does_not_save_cookies() {
+    let gateway = MockGateway::start();
+    gateway.route(
+        "GET",
+        "/api/v1/status",
+        Response::json(200, json!({"status": "ok", "primary_key": "id"}))
+            .header("Set-Cookie", "session=abc; Path=/"),
+    );
+    let jwctl = Jwctl::gateway(&gateway);
+    let cassette = jwctl.config_dir().with_file_name("session.yaml");
+    let cassette = cassette.to_str().unwrap();
+
+    jwctl.success(&["--record", cassette, "status"]);
+    let gateways = jwctl.config_dir().join("gateways");
+    assert!(gateways.exists(), "cookies were not saved when recording");
+    fs::remove_dir_all(&gateways).unwrap();
+
+    let stdout = jwctl.success(&["--replay", cassette, "status"]);
+    assert!(stdout.contains("\"primary_key\": \"id\""), "{stdout}");
+    assert!(!gateways.exists(), "replay saved cookies");
+}